@@ -0,0 +1,301 @@
+use super::{Entry, MyError, MyResult};
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::io::{self, Write};
+use std::os::unix::prelude::*;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+mod printf;
+pub use printf::Format;
+
+/// Placeholder replaced by the path in `--exec` and `--execdir` commands.
+const PLACEHOLDER: &str = "{}";
+
+/// Headroom kept below `ARG_MAX`, as xargs does.
+const ARG_MAX_HEADROOM: usize = 2048;
+
+/// The minimum `ARG_MAX` guaranteed by POSIX.
+const POSIX_ARG_MAX: usize = 4096;
+
+fn arg_max() -> usize {
+    // SAFETY: sysconf has no preconditions.
+    let arg_max = unsafe { libc::sysconf(libc::_SC_ARG_MAX) };
+    usize::try_from(arg_max)
+        .ok()
+        .filter(|&n| n > 0)
+        .unwrap_or(POSIX_ARG_MAX)
+}
+
+/// Size an argument occupies in the exec argument area: the string, its NUL and its pointer.
+fn arg_size(arg: &OsStr) -> usize {
+    arg.len() + 1 + size_of::<usize>()
+}
+
+fn env_size() -> usize {
+    std::env::vars_os()
+        .map(|(key, value)| key.len() + value.len() + 2 + size_of::<usize>())
+        .sum()
+}
+
+/// Runs `argv`, returning whether the command succeeded. Failing to start it is an error.
+fn run_command(argv: &[OsString], dir: Option<&Path>) -> MyResult<bool> {
+    // Keep our output in order with the child's.
+    let _ = io::stdout().flush();
+
+    let mut command = Command::new(&argv[0]);
+    command.args(&argv[1..]);
+    if let Some(dir) = dir {
+        command.current_dir(dir);
+    }
+    let status = command.status().map_err(|e| MyError::new(&argv[0], e))?;
+    Ok(status.success())
+}
+
+/// Replaces every `{}` in `arg` with `path`.
+fn substitute(arg: &str, path: &OsStr) -> OsString {
+    let mut parts = arg.split(PLACEHOLDER);
+    let mut result = OsString::from(parts.next().unwrap_or_default());
+    for part in parts {
+        result.push(path);
+        result.push(part);
+    }
+    result
+}
+
+/// Splits `path` into the directory `--execdir` runs in and the path passed to the command.
+fn split_for_execdir(path: &Path) -> (PathBuf, OsString) {
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_owned(),
+        _ => PathBuf::from("."),
+    };
+    let name = match path.file_name() {
+        Some(name) => Path::new(".").join(name).into_os_string(),
+        None => path.as_os_str().to_owned(),
+    };
+    (dir, name)
+}
+
+/// Paths collected for a `{} +` command, flushed before they would exceed `ARG_MAX`.
+#[derive(Debug)]
+struct Batch {
+    paths: Vec<OsString>,
+    size: usize,
+    limit: usize,
+    dir: Option<PathBuf>,
+}
+
+impl Batch {
+    fn new(template: &[String]) -> Batch {
+        let template_size: usize = template.iter().map(|arg| arg_size(arg.as_ref())).sum();
+        let limit = arg_max()
+            .saturating_sub(env_size())
+            .saturating_sub(template_size)
+            .saturating_sub(ARG_MAX_HEADROOM);
+        Batch {
+            paths: Vec::new(),
+            size: 0,
+            limit,
+            dir: None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Exec {
+    template: Vec<String>,
+    in_dir: bool,
+    batch: Option<Batch>,
+}
+
+impl Exec {
+    /// Builds a command from the values of `--exec` or `--execdir`. A trailing `{} +` selects
+    /// the batched form, which passes as many paths to one command as `ARG_MAX` allows. Like
+    /// find, the batched form needs a command before `{} +`.
+    pub fn new(mut values: Vec<String>, in_dir: bool) -> Result<Exec, &'static str> {
+        let is_batch = values.ends_with(&[PLACEHOLDER.into(), "+".into()]);
+        let batch = if is_batch {
+            values.truncate(values.len() - 2);
+            if values.is_empty() {
+                return Err("missing command before \"{} +\"");
+            }
+            Some(Batch::new(&values))
+        } else {
+            None
+        };
+        Ok(Exec {
+            template: values,
+            in_dir,
+            batch,
+        })
+    }
+
+    /// Returns the directory to run in and the argument standing for `path`.
+    fn target(&self, path: &Path) -> (Option<PathBuf>, OsString) {
+        if self.in_dir {
+            let (dir, name) = split_for_execdir(path);
+            (Some(dir), name)
+        } else {
+            (None, path.as_os_str().to_owned())
+        }
+    }
+
+    fn execute_one(&self, path: &Path) -> MyResult<bool> {
+        let (dir, path) = self.target(path);
+        let argv = self
+            .template
+            .iter()
+            .map(|arg| substitute(arg, &path))
+            .collect::<Vec<_>>();
+        run_command(&argv, dir.as_deref())
+    }
+
+    /// Runs the batched command on the collected paths. Unlike the per-path form, a failing
+    /// command is an error, as in find.
+    fn flush(&mut self) -> MyResult<()> {
+        let Some(batch) = self.batch.as_mut() else {
+            return Ok(());
+        };
+        if batch.paths.is_empty() {
+            return Ok(());
+        }
+        let mut argv = self.template.iter().map(OsString::from).collect::<Vec<_>>();
+        argv.append(&mut batch.paths);
+        batch.size = 0;
+        if run_command(&argv, batch.dir.as_deref())? {
+            Ok(())
+        } else {
+            let e = io::Error::other("command exited with non-zero status");
+            Err(MyError::new(&argv[0], e))
+        }
+    }
+
+    fn push(&mut self, path: &Path) -> MyResult<()> {
+        let (dir, arg) = self.target(path);
+        let size = arg_size(&arg);
+
+        let batch = self.batch.as_ref().expect("batch should exist");
+        let result = if batch.dir != dir || batch.size + size > batch.limit {
+            self.flush()
+        } else {
+            Ok(())
+        };
+
+        let batch = self.batch.as_mut().expect("batch should exist");
+        batch.dir = dir;
+        batch.size += size;
+        batch.paths.push(arg);
+        result
+    }
+
+    fn apply(&mut self, entry: &Entry) -> MyResult<bool> {
+        if self.batch.is_some() {
+            // Like find, the batched form is always true; its command runs later.
+            self.push(entry.path()).map(|()| true)
+        } else {
+            self.execute_one(entry.path())
+        }
+    }
+}
+
+fn delete(path: &Path) -> io::Result<()> {
+    if path.symlink_metadata()?.is_dir() {
+        fs::remove_dir(path)
+    } else {
+        fs::remove_file(path)
+    }
+}
+
+fn write_stdout(buf: &[u8]) -> MyResult<bool> {
+    io::stdout()
+        .lock()
+        .write_all(buf)
+        .map(|()| true)
+        .map_err(|e| MyError::new("standard output", e))
+}
+
+/// An action applied to each path that passes the tests. Like find's implicit `-a`, the actions
+/// of a path run in order until one of them is false or fails.
+#[derive(Debug)]
+pub enum Action {
+    Exec(Exec),
+    Delete,
+    Print,
+    Print0,
+    Printf(Format),
+}
+
+impl Action {
+    pub fn is_delete(&self) -> bool {
+        matches!(self, Action::Delete)
+    }
+
+    /// Applies the action to `entry`, returning whether the following actions should run.
+    pub fn apply(&mut self, entry: &Entry) -> MyResult<bool> {
+        let path = entry.path();
+        match self {
+            Action::Exec(exec) => exec.apply(entry),
+            Action::Delete => delete(path)
+                .map(|()| true)
+                .map_err(|e| MyError::new(path, e)),
+            Action::Print => write_stdout(&[path.as_os_str().as_bytes(), b"\n"].concat()),
+            Action::Print0 => write_stdout(&[path.as_os_str().as_bytes(), b"\0"].concat()),
+            Action::Printf(format) => {
+                let buf = format.render(entry).map_err(|e| MyError::new(path, e))?;
+                write_stdout(&buf)
+            }
+        }
+    }
+
+    /// Runs the commands still pending in batched `--exec`s.
+    pub fn finish(&mut self) -> MyResult<()> {
+        match self {
+            Action::Exec(exec) => exec.flush(),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exec_batch() {
+        let exec = Exec::new(vec!["echo".into(), "{}".into(), "+".into()], false).unwrap();
+        assert_eq!(exec.template, vec!["echo"]);
+        assert!(exec.batch.is_some());
+    }
+
+    #[test]
+    fn test_exec_single() {
+        let exec = Exec::new(vec!["echo".into(), "{}".into()], false).unwrap();
+        assert_eq!(exec.template, vec!["echo", "{}"]);
+        assert!(exec.batch.is_none());
+    }
+
+    #[test]
+    fn test_exec_batch_without_command() {
+        assert!(Exec::new(vec!["{}".into(), "+".into()], false).is_err());
+    }
+
+    #[test]
+    fn test_substitute() {
+        let path = OsStr::new("a.txt");
+        assert_eq!(substitute("{}", path), "a.txt");
+        assert_eq!(substitute("x{}y{}", path), "xa.txtya.txt");
+        assert_eq!(substitute("-v", path), "-v");
+    }
+
+    #[test]
+    fn test_split_for_execdir() {
+        assert_eq!(
+            split_for_execdir(Path::new("a/b/c.txt")),
+            (PathBuf::from("a/b"), OsString::from("./c.txt"))
+        );
+        assert_eq!(
+            split_for_execdir(Path::new("c.txt")),
+            (PathBuf::from("."), OsString::from("./c.txt"))
+        );
+    }
+}
@@ -0,0 +1,281 @@
+use crate::Entry;
+use chrono::{DateTime, Local};
+use std::fs::Metadata;
+use std::os::unix::prelude::*;
+use std::path::Path;
+use std::str::FromStr;
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum FormatError {
+    #[error("unrecognized directive '%{0}'")]
+    UnknownDirective(char),
+
+    #[error("unrecognized escape '\\{0}'")]
+    UnknownEscape(char),
+
+    #[error("format ends with an incomplete directive")]
+    Incomplete,
+
+    #[error("field width is larger than {MAX_WIDTH}")]
+    WidthOutOfRange,
+}
+
+/// The largest field width accepted in a directive.
+const MAX_WIDTH: usize = 4096;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Directive {
+    Path,
+    Name,
+    Dir,
+    Size,
+    Mode,
+    User,
+    Group,
+    Time,
+    Depth,
+}
+
+impl Directive {
+    fn needs_metadata(self) -> bool {
+        !matches!(
+            self,
+            Directive::Path | Directive::Name | Directive::Dir | Directive::Depth
+        )
+    }
+}
+
+impl TryFrom<char> for Directive {
+    type Error = FormatError;
+
+    fn try_from(c: char) -> Result<Directive, FormatError> {
+        Ok(match c {
+            'p' => Directive::Path,
+            'f' => Directive::Name,
+            'h' => Directive::Dir,
+            's' => Directive::Size,
+            'm' => Directive::Mode,
+            'u' => Directive::User,
+            'g' => Directive::Group,
+            't' => Directive::Time,
+            'd' => Directive::Depth,
+            _ => return Err(FormatError::UnknownDirective(c)),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Piece {
+    Literal(String),
+    Field {
+        directive: Directive,
+        left_align: bool,
+        width: usize,
+    },
+}
+
+/// A `-printf` format: literal text interleaved with `%[-][WIDTH]X` directives.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Format(Vec<Piece>);
+
+impl FromStr for Format {
+    type Err = FormatError;
+
+    fn from_str(s: &str) -> Result<Format, FormatError> {
+        let mut pieces = Vec::new();
+        let mut literal = String::new();
+        let mut chars = s.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => literal.push(match chars.next().ok_or(FormatError::Incomplete)? {
+                    'n' => '\n',
+                    't' => '\t',
+                    'r' => '\r',
+                    '0' => '\0',
+                    '\\' => '\\',
+                    c => return Err(FormatError::UnknownEscape(c)),
+                }),
+                '%' if chars.peek() == Some(&'%') => {
+                    chars.next();
+                    literal.push('%');
+                }
+                '%' => {
+                    let left_align = chars.next_if_eq(&'-').is_some();
+                    let mut width: usize = 0;
+                    while let Some(d) = chars.peek().and_then(|c| c.to_digit(10)) {
+                        width = width
+                            .checked_mul(10)
+                            .and_then(|w| w.checked_add(d as usize))
+                            .filter(|&w| w <= MAX_WIDTH)
+                            .ok_or(FormatError::WidthOutOfRange)?;
+                        chars.next();
+                    }
+                    let directive = chars.next().ok_or(FormatError::Incomplete)?.try_into()?;
+                    if !literal.is_empty() {
+                        pieces.push(Piece::Literal(std::mem::take(&mut literal)));
+                    }
+                    pieces.push(Piece::Field {
+                        directive,
+                        left_align,
+                        width,
+                    });
+                }
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            pieces.push(Piece::Literal(literal));
+        }
+        Ok(Format(pieces))
+    }
+}
+
+impl Format {
+    fn needs_metadata(&self) -> bool {
+        self.0.iter().any(|piece| match piece {
+            Piece::Literal(_) => false,
+            Piece::Field { directive, .. } => directive.needs_metadata(),
+        })
+    }
+
+    /// Expands the format for `entry`. Metadata is only read when a directive needs it.
+    pub fn render(&self, entry: &Entry) -> std::io::Result<Vec<u8>> {
+        let metadata = match entry.metadata() {
+            Some(metadata) => Some(metadata.clone()),
+            None if self.needs_metadata() => Some(entry.path().symlink_metadata()?),
+            None => None,
+        };
+
+        let mut buf = Vec::new();
+        for piece in &self.0 {
+            match piece {
+                Piece::Literal(s) => buf.extend_from_slice(s.as_bytes()),
+                Piece::Field {
+                    directive,
+                    left_align,
+                    width,
+                } => {
+                    let value = expand(*directive, entry, metadata.as_ref());
+                    let padding = b" ".repeat(width.saturating_sub(value.len()));
+                    if *left_align {
+                        buf.extend_from_slice(&value);
+                        buf.extend_from_slice(&padding);
+                    } else {
+                        buf.extend_from_slice(&padding);
+                        buf.extend_from_slice(&value);
+                    }
+                }
+            }
+        }
+        Ok(buf)
+    }
+}
+
+fn file_name(path: &Path) -> &[u8] {
+    path.file_name()
+        .map_or(path.as_os_str(), |name| name)
+        .as_bytes()
+}
+
+fn leading_dirs(path: &Path) -> &[u8] {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.as_os_str().as_bytes(),
+        _ => b".",
+    }
+}
+
+fn user_name(uid: u32) -> Vec<u8> {
+    uzers::get_user_by_uid(uid)
+        .map(|user| user.name().as_bytes().to_vec())
+        .unwrap_or_else(|| uid.to_string().into_bytes())
+}
+
+fn group_name(gid: u32) -> Vec<u8> {
+    uzers::get_group_by_gid(gid)
+        .map(|group| group.name().as_bytes().to_vec())
+        .unwrap_or_else(|| gid.to_string().into_bytes())
+}
+
+/// Formats the modification time like ctime(3), with nanoseconds as GNU find does.
+fn mtime(metadata: &Metadata) -> Vec<u8> {
+    match metadata.modified() {
+        Ok(time) => DateTime::<Local>::from(time)
+            .format("%a %b %e %H:%M:%S.%f0 %Y")
+            .to_string()
+            .into_bytes(),
+        Err(_) => Vec::new(),
+    }
+}
+
+fn expand(directive: Directive, entry: &Entry, metadata: Option<&Metadata>) -> Vec<u8> {
+    let path = entry.path();
+    let metadata = || metadata.expect("metadata should be loaded");
+    match directive {
+        Directive::Path => path.as_os_str().as_bytes().to_vec(),
+        Directive::Name => file_name(path).to_vec(),
+        Directive::Dir => leading_dirs(path).to_vec(),
+        Directive::Size => metadata().len().to_string().into_bytes(),
+        Directive::Mode => format!("{:o}", metadata().mode() & 0o7777).into_bytes(),
+        Directive::User => user_name(metadata().uid()),
+        Directive::Group => group_name(metadata().gid()),
+        Directive::Time => mtime(metadata()),
+        Directive::Depth => entry.depth().to_string().into_bytes(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(format: &str, path: &str, depth: usize) -> String {
+        let format = format.parse::<Format>().unwrap();
+        let entry = Entry::new(path, depth);
+        String::from_utf8(format.render(&entry).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            "%x".parse::<Format>(),
+            Err(FormatError::UnknownDirective('x'))
+        );
+        assert_eq!(
+            "\\q".parse::<Format>(),
+            Err(FormatError::UnknownEscape('q'))
+        );
+        assert_eq!("abc%".parse::<Format>(), Err(FormatError::Incomplete));
+        assert_eq!("abc%-5".parse::<Format>(), Err(FormatError::Incomplete));
+        assert_eq!(
+            "%99999999999999999999p".parse::<Format>(),
+            Err(FormatError::WidthOutOfRange)
+        );
+        assert_eq!(
+            "%4097p".parse::<Format>(),
+            Err(FormatError::WidthOutOfRange)
+        );
+        assert!("%4096p".parse::<Format>().is_ok());
+    }
+
+    #[test]
+    fn test_render_names() {
+        let actual = render("%p|%f|%h|%d\\n", "tests/inputs/a/a.txt", 1);
+        assert_eq!(actual, "tests/inputs/a/a.txt|a.txt|tests/inputs/a|1\n");
+    }
+
+    #[test]
+    fn test_render_without_slash() {
+        assert_eq!(render("%f %h", "tests", 0), "tests .");
+    }
+
+    #[test]
+    fn test_render_metadata() {
+        assert_eq!(render("%s", "tests/inputs/a/a.txt", 0), "2");
+    }
+
+    #[test]
+    fn test_render_width_and_percent() {
+        assert_eq!(render("[%4d][%-4d]%%", "tests", 12), "[  12][12  ]%");
+    }
+}
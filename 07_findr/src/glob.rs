@@ -0,0 +1,309 @@
+use std::str::FromStr;
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum GlobError {
+    #[error("unterminated character class in '{0}'")]
+    UnterminatedClass(String),
+
+    #[error("unknown character class '[:{0}:]'")]
+    UnknownClass(String),
+
+    #[error("pattern ends with an escape character: '{0}'")]
+    TrailingEscape(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Named {
+    Alnum,
+    Alpha,
+    Digit,
+    Lower,
+    Punct,
+    Space,
+    Upper,
+    XDigit,
+}
+
+impl Named {
+    fn parse(name: &str) -> Option<Named> {
+        Some(match name {
+            "alnum" => Named::Alnum,
+            "alpha" => Named::Alpha,
+            "digit" => Named::Digit,
+            "lower" => Named::Lower,
+            "punct" => Named::Punct,
+            "space" => Named::Space,
+            "upper" => Named::Upper,
+            "xdigit" => Named::XDigit,
+            _ => return None,
+        })
+    }
+
+    fn contains(self, c: char) -> bool {
+        match self {
+            Named::Alnum => c.is_alphanumeric(),
+            Named::Alpha => c.is_alphabetic(),
+            Named::Digit => c.is_ascii_digit(),
+            Named::Lower => c.is_lowercase(),
+            Named::Punct => c.is_ascii_punctuation(),
+            Named::Space => c.is_whitespace(),
+            Named::Upper => c.is_uppercase(),
+            Named::XDigit => c.is_ascii_hexdigit(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ClassItem {
+    Range(char, char),
+    Named(Named),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Literal(char),
+    AnyChar,
+    AnyString,
+    Class {
+        negated: bool,
+        items: Vec<ClassItem>,
+    },
+}
+
+/// A shell wildcard pattern as understood by fnmatch(3): `*`, `?`, bracket expressions with
+/// ranges, negation and named classes, and `\` to quote the next character.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Glob {
+    tokens: Vec<Token>,
+    case_insensitive: bool,
+    literal_leading_dot: bool,
+}
+
+fn parse_class(
+    pattern: &str,
+    chars: &mut std::iter::Peekable<std::str::Chars>,
+) -> Result<Token, GlobError> {
+    let unterminated = || GlobError::UnterminatedClass(pattern.into());
+    let negated = chars.next_if(|&c| c == '!' || c == '^').is_some();
+    let mut items = Vec::new();
+    let mut first = true;
+    loop {
+        let c = chars.next().ok_or_else(unterminated)?;
+        match c {
+            ']' if !first => break,
+            '[' if chars.peek() == Some(&':') => {
+                chars.next();
+                let mut name = String::new();
+                loop {
+                    match chars.next().ok_or_else(unterminated)? {
+                        ':' if chars.peek() == Some(&']') => break,
+                        c => name.push(c),
+                    }
+                }
+                chars.next();
+                let named = Named::parse(&name).ok_or(GlobError::UnknownClass(name))?;
+                items.push(ClassItem::Named(named));
+            }
+            c => {
+                let c = if c == '\\' {
+                    chars.next().ok_or_else(unterminated)?
+                } else {
+                    c
+                };
+                let mut lookahead = chars.clone();
+                if lookahead.next() == Some('-') && lookahead.peek().is_some_and(|&e| e != ']') {
+                    chars.next();
+                    let end = chars.next().ok_or_else(unterminated)?;
+                    items.push(ClassItem::Range(c, end));
+                } else {
+                    items.push(ClassItem::Range(c, c));
+                }
+            }
+        }
+        first = false;
+    }
+    Ok(Token::Class { negated, items })
+}
+
+impl FromStr for Glob {
+    type Err = GlobError;
+
+    fn from_str(pattern: &str) -> Result<Glob, GlobError> {
+        let mut tokens = Vec::new();
+        let mut chars = pattern.chars().peekable();
+        while let Some(c) = chars.next() {
+            let token = match c {
+                '*' => Token::AnyString,
+                '?' => Token::AnyChar,
+                '[' => parse_class(pattern, &mut chars)?,
+                '\\' => match chars.next() {
+                    Some(c) => Token::Literal(c),
+                    None => return Err(GlobError::TrailingEscape(pattern.into())),
+                },
+                c => Token::Literal(c),
+            };
+            // Consecutive stars are equivalent to a single one.
+            if !(token == Token::AnyString && tokens.last() == Some(&Token::AnyString)) {
+                tokens.push(token);
+            }
+        }
+        Ok(Glob {
+            tokens,
+            case_insensitive: false,
+            literal_leading_dot: false,
+        })
+    }
+}
+
+fn eq_ignore_case(a: char, b: char) -> bool {
+    a == b || a.to_lowercase().eq(b.to_lowercase())
+}
+
+impl Glob {
+    pub fn case_insensitive(mut self, yes: bool) -> Glob {
+        self.case_insensitive = yes;
+        self
+    }
+
+    /// Require a leading `.` to be matched by a literal `.`, as shells do for file names.
+    pub fn literal_leading_dot(mut self, yes: bool) -> Glob {
+        self.literal_leading_dot = yes;
+        self
+    }
+
+    fn class_contains(&self, items: &[ClassItem], c: char) -> bool {
+        let candidates = if self.case_insensitive {
+            c.to_lowercase()
+                .chain(c.to_uppercase())
+                .chain([c])
+                .collect()
+        } else {
+            vec![c]
+        };
+        items.iter().any(|item| {
+            candidates.iter().any(|&c| match *item {
+                ClassItem::Range(start, end) => start <= c && c <= end,
+                ClassItem::Named(named) => named.contains(c),
+            })
+        })
+    }
+
+    fn matches_char(&self, token: &Token, c: char) -> bool {
+        match token {
+            Token::Literal(l) if self.case_insensitive => eq_ignore_case(*l, c),
+            Token::Literal(l) => *l == c,
+            Token::AnyChar => true,
+            Token::Class { negated, items } => self.class_contains(items, c) != *negated,
+            Token::AnyString => unreachable!(),
+        }
+    }
+
+    pub fn is_match(&self, s: &str) -> bool {
+        if self.literal_leading_dot
+            && s.starts_with('.')
+            && !matches!(self.tokens.first(), Some(Token::Literal('.')))
+        {
+            return false;
+        }
+
+        let text = s.chars().collect::<Vec<_>>();
+        let (mut t, mut p) = (0, 0);
+        // Where to resume after the last `*`: the pattern after it and the text it absorbed.
+        let mut backtrack = None;
+        while t < text.len() {
+            match self.tokens.get(p) {
+                Some(Token::AnyString) => {
+                    p += 1;
+                    backtrack = Some((p, t));
+                }
+                Some(token) if self.matches_char(token, text[t]) => {
+                    p += 1;
+                    t += 1;
+                }
+                _ => match backtrack {
+                    Some((star_p, star_t)) => {
+                        p = star_p;
+                        t = star_t + 1;
+                        backtrack = Some((star_p, t));
+                    }
+                    None => return false,
+                },
+            }
+        }
+        self.tokens[p..]
+            .iter()
+            .all(|token| *token == Token::AnyString)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn glob(pattern: &str) -> Glob {
+        pattern.parse().unwrap()
+    }
+
+    #[test]
+    fn test_wildcards() {
+        assert!(glob("*.rs").is_match("lib.rs"));
+        assert!(glob("*.rs").is_match(".rs"));
+        assert!(!glob("*.rs").is_match("lib.rsx"));
+        assert!(glob("a*b*c").is_match("aXbYbZc"));
+        assert!(!glob("a*b*c").is_match("aXbYbZ"));
+        assert!(glob("?.txt").is_match("a.txt"));
+        assert!(!glob("?.txt").is_match("ab.txt"));
+        assert!(glob("**").is_match(""));
+    }
+
+    #[test]
+    fn test_classes() {
+        assert!(glob("[abc].txt").is_match("b.txt"));
+        assert!(!glob("[!abc].txt").is_match("b.txt"));
+        assert!(glob("[^abc].txt").is_match("d.txt"));
+        assert!(glob("[a-c]").is_match("b"));
+        assert!(glob("[]]").is_match("]"));
+        assert!(glob("[a-]").is_match("-"));
+        assert!(glob("[[:digit:]]*").is_match("1st"));
+        assert!(!glob("[[:upper:]]*").is_match("lower"));
+    }
+
+    #[test]
+    fn test_escape() {
+        assert!(glob("\\*").is_match("*"));
+        assert!(!glob("\\*").is_match("a"));
+    }
+
+    #[test]
+    fn test_case_insensitive() {
+        assert!(glob("*.CSV").case_insensitive(true).is_match("b.csv"));
+        assert!(glob("[A-C].csv").case_insensitive(true).is_match("b.csv"));
+        assert!(!glob("*.CSV").is_match("b.csv"));
+    }
+
+    #[test]
+    fn test_leading_dot() {
+        let star = glob("*").literal_leading_dot(true);
+        assert!(!star.is_match(".hidden"));
+        assert!(star.is_match("visible"));
+        assert!(glob(".*").literal_leading_dot(true).is_match(".hidden"));
+        assert!(glob("*").is_match(".hidden"));
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            "[abc".parse::<Glob>(),
+            Err(GlobError::UnterminatedClass("[abc".into()))
+        );
+        assert_eq!(
+            "[[:foo:]]".parse::<Glob>(),
+            Err(GlobError::UnknownClass("foo".into()))
+        );
+        assert_eq!(
+            "abc\\".parse::<Glob>(),
+            Err(GlobError::TrailingEscape("abc\\".into()))
+        );
+    }
+}
@@ -1,12 +1,24 @@
-use clap::{Parser, ValueEnum, builder::PossibleValue};
-use regex::Regex;
-use std::collections::VecDeque;
-use std::ffi::OsStr;
+use clap::{
+    Arg, ArgAction, ArgMatches, Command, CommandFactory, FromArgMatches, Parser, ValueEnum,
+    builder::PossibleValue, error::ErrorKind, value_parser,
+};
+use regex::{Regex, RegexBuilder};
+use std::ffi::OsString;
 use std::fs;
 use std::io;
-use std::path::{Path, PathBuf};
+use std::num::NonZeroUsize;
+use std::path::Path;
 use thiserror::Error;
 
+mod action;
+use action::{Action, Exec, Format};
+
+mod glob;
+use glob::Glob;
+
+mod walk;
+pub use walk::{Entry, Follow, ParallelIter, PathIter, Sort};
+
 #[derive(Debug, Error)]
 #[error("{path}: {source}")]
 pub struct MyError {
@@ -31,12 +43,12 @@ pub enum Type {
     Other,
 }
 
-impl From<&Path> for Type {
-    fn from(value: &Path) -> Type {
-        if value.is_dir() {
-            Type::Dir
-        } else if value.is_symlink() {
+impl From<fs::FileType> for Type {
+    fn from(value: fs::FileType) -> Type {
+        if value.is_symlink() {
             Type::Link
+        } else if value.is_dir() {
+            Type::Dir
         } else if value.is_file() {
             Type::File
         } else {
@@ -45,6 +57,15 @@ impl From<&Path> for Type {
     }
 }
 
+impl From<&Path> for Type {
+    /// Classifies `path` itself, without following a symbolic link.
+    fn from(value: &Path) -> Type {
+        value
+            .symlink_metadata()
+            .map_or(Type::Other, |metadata| Type::from(metadata.file_type()))
+    }
+}
+
 impl ValueEnum for Type {
     fn value_variants<'a>() -> &'a [Self] {
         &[Type::Dir, Type::File, Type::Link]
@@ -62,147 +83,488 @@ impl ValueEnum for Type {
 
 #[derive(Debug, Parser)]
 #[command(version, about, long_about = None)]
-pub struct Args {
+struct Options {
     /// Search path(s)
     #[arg(value_name = "PATH")]
     pathes: Vec<String>,
 
-    /// Name(s)
-    #[arg(long = "name", short = 'n', value_name = "NAME")]
-    names: Vec<Regex>,
+    /// File name(s) matching a shell pattern
+    #[arg(long = "name", short = 'n', value_name = "PATTERN")]
+    names: Vec<Glob>,
+
+    /// Like --name, but the match is case insensitive
+    #[arg(long = "iname", value_name = "PATTERN")]
+    inames: Vec<Glob>,
+
+    /// Whole path(s) matching a shell pattern
+    #[arg(long = "path", value_name = "PATTERN")]
+    paths: Vec<Glob>,
+
+    /// Like --path, but the match is case insensitive
+    #[arg(long = "ipath", value_name = "PATTERN")]
+    ipaths: Vec<Glob>,
+
+    /// Whole path(s) matching a regular expression
+    #[arg(long = "regex", value_name = "REGEX", value_parser = parse_regex)]
+    regexes: Vec<Regex>,
+
+    /// Like --regex, but the match is case insensitive
+    #[arg(long = "iregex", value_name = "REGEX", value_parser = parse_iregex)]
+    iregexes: Vec<Regex>,
 
     /// Type(s)
     #[arg(long = "type", short = 't', value_name = "TYPE", num_args(0..))]
     types: Vec<Type>,
+
+    /// Never follow symbolic links (the default)
+    #[arg(short = 'P', overrides_with_all = ["follow_command_line", "follow_all"])]
+    never_follow: bool,
+
+    /// Follow symbolic links given as search paths, but no others
+    #[arg(short = 'H', overrides_with_all = ["never_follow", "follow_all"])]
+    follow_command_line: bool,
+
+    /// Follow all symbolic links
+    #[arg(short = 'L', overrides_with_all = ["never_follow", "follow_command_line"])]
+    follow_all: bool,
+
+    /// Do not descend into directories on other file systems
+    #[arg(long = "xdev", visible_alias = "mount")]
+    same_file_system: bool,
+
+    /// Skip paths ignored by .gitignore, .ignore and .git/info/exclude files
+    #[arg(long = "respect-ignore")]
+    respect_ignore: bool,
+
+    /// Order of the entries of each directory
+    #[arg(long = "sort", value_name = "ORDER", value_enum, default_value_t = Sort::None)]
+    sort: Sort,
+
+    /// Read directories with N threads. The paths of a directory are printed once it has been
+    /// read, and the directories read ahead of the output are kept in memory
+    #[arg(long = "jobs", short = 'j', value_name = "N")]
+    jobs: Option<NonZeroUsize>,
+
+    /// With --jobs, print paths as soon as they are found instead of in --sort order
+    #[arg(long = "unordered", requires = "jobs")]
+    unordered: bool,
 }
 
-pub struct PathIter {
-    /// Errors encountered when traverse the file tree. If it's not empty, these errors
-    /// should be reported at first.
-    errors: VecDeque<MyResult<PathBuf>>,
+/// Compiles a regular expression that must match a whole path, as find's `-regex` does.
+fn parse_regex(s: &str) -> Result<Regex, regex::Error> {
+    Regex::new(&format!("^(?:{s})$"))
+}
 
-    to_be_visit: Vec<PathBuf>,
+fn parse_iregex(s: &str) -> Result<Regex, regex::Error> {
+    RegexBuilder::new(&format!("^(?:{s})$"))
+        .case_insensitive(true)
+        .build()
 }
 
-impl PathIter {
-    fn push_err(&mut self, path: &Path, err: io::Error) {
-        let err = MyError::new(path, err);
-        self.errors.push_back(Err(err));
+impl Options {
+    /// File name patterns. Like a shell, `*` and `?` do not match a leading dot.
+    fn name_globs(&self) -> Vec<Glob> {
+        let names = self.names.iter().cloned();
+        let inames = self.inames.iter().map(|g| g.clone().case_insensitive(true));
+        names
+            .chain(inames)
+            .map(|g| g.literal_leading_dot(true))
+            .collect()
     }
 
-    fn read_dir(&mut self, path: &Path) -> Vec<PathBuf> {
-        match fs::read_dir(path) {
-            Err(e) => {
-                self.push_err(path, e);
-                Vec::new()
-            }
-            Ok(iter) => iter
-                .filter_map(|x| match x {
-                    Err(e) => {
-                        self.push_err(path, e);
-                        None
-                    }
-                    Ok(v) => Some(v.path()),
-                })
-                .collect(),
+    fn path_globs(&self) -> Vec<Glob> {
+        let paths = self.paths.iter().cloned();
+        let ipaths = self.ipaths.iter().map(|g| g.clone().case_insensitive(true));
+        paths.chain(ipaths).collect()
+    }
+
+    fn path_regexes(&self) -> Vec<Regex> {
+        self.regexes.iter().chain(&self.iregexes).cloned().collect()
+    }
+
+    fn follow(&self) -> Follow {
+        match (self.never_follow, self.follow_command_line, self.follow_all) {
+            (_, _, true) => Follow::Always,
+            (_, true, _) => Follow::CommandLine,
+            _ => Follow::Never,
         }
     }
 }
 
-impl<T> From<T> for PathIter
+/// The action arguments. They may be repeated and are applied in command-line order, so they
+/// are collected from the `ArgMatches` by `actions_from` rather than into fields.
+fn action_args() -> [Arg; 6] {
+    let command = |id: &'static str| {
+        Arg::new(id)
+            .long(id)
+            .value_name("COMMAND")
+            .num_args(1..)
+            .allow_hyphen_values(true)
+            .value_terminator(";")
+            .action(ArgAction::Append)
+    };
+    let flag = |id: &'static str| {
+        Arg::new(id)
+            .long(id)
+            .num_args(0)
+            .default_missing_value("")
+            .action(ArgAction::Append)
+    };
+    [
+        command("exec").help(
+            "Execute COMMAND for each path, replacing \"{}\" with the path. The command is \
+            terminated by \";\", or by \"{} +\" to pass as many paths at once as the system allows",
+        ),
+        command("execdir")
+            .help("Like --exec, but run COMMAND in the directory containing the path"),
+        flag("delete")
+            .conflicts_with("unordered")
+            .help("Delete files and empty directories; implies depth-first traversal"),
+        flag("print").help("Print the path followed by a newline (the default action)"),
+        flag("print0").help("Print the path followed by a null character"),
+        Arg::new("printf")
+            .long("printf")
+            .value_name("FORMAT")
+            .value_parser(value_parser!(Format))
+            .action(ArgAction::Append)
+            .help("Print FORMAT, expanding the directives %p %f %h %s %m %u %g %t %d"),
+    ]
+}
+
+#[derive(Debug)]
+pub struct Args {
+    options: Options,
+
+    /// Actions in command-line order
+    actions: Vec<Action>,
+}
+
+impl FromArgMatches for Args {
+    fn from_arg_matches(matches: &ArgMatches) -> Result<Args, clap::Error> {
+        Ok(Args {
+            options: Options::from_arg_matches(matches)?,
+            actions: actions_from(matches)?,
+        })
+    }
+
+    fn update_from_arg_matches(&mut self, matches: &ArgMatches) -> Result<(), clap::Error> {
+        *self = Args::from_arg_matches(matches)?;
+        Ok(())
+    }
+}
+
+impl CommandFactory for Args {
+    fn command() -> Command {
+        Options::command().args(action_args())
+    }
+
+    fn command_for_update() -> Command {
+        Options::command_for_update().args(action_args())
+    }
+}
+
+impl Parser for Args {
+    fn parse() -> Args {
+        Args::parse_from(std::env::args_os())
+    }
+
+    fn try_parse() -> Result<Args, clap::Error> {
+        Args::try_parse_from(std::env::args_os())
+    }
+
+    fn parse_from<I, T>(itr: I) -> Args
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString> + Clone,
+    {
+        Args::try_parse_from(itr).unwrap_or_else(|e| e.exit())
+    }
+
+    fn try_parse_from<I, T>(itr: I) -> Result<Args, clap::Error>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString> + Clone,
+    {
+        let args = terminate_exec_args(itr.into_iter().map(Into::into));
+        let matches = Args::command().try_get_matches_from(args)?;
+        Args::from_arg_matches(&matches).map_err(|e| e.format(&mut Args::command()))
+    }
+}
+
+/// Builds an action for each occurrence of an action argument, sorted by its position.
+fn actions_from(matches: &ArgMatches) -> Result<Vec<Action>, clap::Error> {
+    let mut actions: Vec<(usize, Action)> = Vec::new();
+
+    for (id, in_dir) in [("exec", false), ("execdir", true)] {
+        let (Some(occurrences), Some(mut indices)) = (
+            matches.get_occurrences::<String>(id),
+            matches.indices_of(id),
+        ) else {
+            continue;
+        };
+        for values in occurrences {
+            let values = values.cloned().collect::<Vec<_>>();
+            let index = indices.next().unwrap_or_default();
+            indices.by_ref().take(values.len() - 1).for_each(drop);
+            let exec = Exec::new(values, in_dir)
+                .map_err(|e| clap::Error::raw(ErrorKind::InvalidValue, format!("--{id}: {e}")))?;
+            actions.push((index, Action::Exec(exec)));
+        }
+    }
+    for id in ["delete", "print", "print0"] {
+        for index in matches.indices_of(id).into_iter().flatten() {
+            let action = match id {
+                "delete" => Action::Delete,
+                "print" => Action::Print,
+                _ => Action::Print0,
+            };
+            actions.push((index, action));
+        }
+    }
+    if let (Some(formats), Some(indices)) = (
+        matches.get_many::<Format>("printf"),
+        matches.indices_of("printf"),
+    ) {
+        let formats = formats.map(|format| Action::Printf(format.clone()));
+        actions.extend(indices.zip(formats));
+    }
+
+    actions.sort_by_key(|(index, _)| *index);
+    Ok(actions.into_iter().map(|(_, action)| action).collect())
+}
+
+/// Prepares the command line for clap: `--exec=COMMAND` is split so that the arguments
+/// following it still belong to the command, and the batched form `{} +` is terminated with
+/// ";" unless the user already did, so that it does not swallow the arguments following it.
+fn terminate_exec_args<I>(args: I) -> Vec<OsString>
 where
-    T: AsRef<Path>,
+    I: IntoIterator<Item = OsString>,
 {
-    fn from(value: T) -> PathIter {
-        PathIter {
-            errors: VecDeque::new(),
-            to_be_visit: vec![value.as_ref().into()],
+    let mut result: Vec<OsString> = Vec::new();
+    let mut in_exec = false;
+    let mut args = args.into_iter().peekable();
+    while let Some(arg) = args.next() {
+        let terminates = in_exec && arg == "+" && result.last().is_some_and(|v| v == "{}");
+        match arg.to_str().map(|s| s.split_once('=').unwrap_or((s, ""))) {
+            Some((name @ ("--exec" | "--execdir"), command)) => {
+                in_exec = true;
+                if arg.len() > name.len() {
+                    result.push(name.into());
+                    result.push(command.into());
+                    continue;
+                }
+            }
+            Some((";", _)) => in_exec = false,
+            _ => in_exec = in_exec && !terminates,
+        }
+        result.push(arg);
+        if terminates && args.peek().is_none_or(|next| next != ";") {
+            result.push(";".into());
         }
     }
+    result
 }
 
-impl Iterator for PathIter {
-    type Item = MyResult<PathBuf>;
+fn is_match_names_or_err(value: &MyResult<Entry>, names: &[Glob]) -> bool {
+    if names.is_empty() {
+        return true;
+    }
+    match value {
+        Err(_) => true,
+        Ok(entry) => {
+            let path = entry.path();
+            let file_name = path.file_name().unwrap_or(path.as_os_str());
+            let file_name = file_name.to_string_lossy();
+            names.iter().any(|glob| glob.is_match(&file_name))
+        }
+    }
+}
 
-    fn next(&mut self) -> Option<Self::Item> {
-        if let Some(next) = self.errors.pop_front() {
-            return Some(next);
+fn is_match_paths_or_err(value: &MyResult<Entry>, paths: &[Glob]) -> bool {
+    if paths.is_empty() {
+        return true;
+    }
+    match value {
+        Err(_) => true,
+        Ok(entry) => {
+            let path = entry.path().to_string_lossy();
+            paths.iter().any(|glob| glob.is_match(&path))
         }
-        self.to_be_visit.pop().map(|path| {
-            let metadata = path.metadata().map_err(|e| MyError::new(&path, e))?;
-            if metadata.is_dir() {
-                let children = self.read_dir(&path);
-                self.to_be_visit.extend(children.into_iter().rev());
-            }
-            Ok(path)
-        })
     }
 }
 
-fn is_match_names_or_err(value: &MyResult<PathBuf>, names: &[Regex]) -> bool {
-    if names.is_empty() {
+fn is_match_regexes_or_err(value: &MyResult<Entry>, regexes: &[Regex]) -> bool {
+    if regexes.is_empty() {
         return true;
     }
     match value {
         Err(_) => true,
-        Ok(path) => path
-            .file_name()
-            .and_then(OsStr::to_str)
-            .is_some_and(|file_name| names.iter().any(|re| re.is_match(file_name))),
+        Ok(entry) => {
+            let path = entry.path().to_string_lossy();
+            regexes.iter().any(|re| re.is_match(&path))
+        }
     }
 }
 
-fn is_match_types_or_err(value: &MyResult<PathBuf>, types: &[Type]) -> bool {
+fn is_match_types_or_err(value: &MyResult<Entry>, types: &[Type]) -> bool {
     if types.is_empty() {
         return true;
     }
     match value {
         Err(_) => true,
-        Ok(path) => types.contains(&Type::from(path.as_ref())),
+        Ok(entry) => types.contains(&entry.file_type()),
     }
 }
 
-pub fn run(args: Args) {
-    for path in &args.pathes {
-        let iter = PathIter::from(path)
-            .filter(|x| is_match_names_or_err(x, &args.names))
-            .filter(|x| is_match_types_or_err(x, &args.types));
+/// Searches the paths and applies the actions, returning the exit status. The status is
+/// non-zero when an action fails. Unlike find, a path that cannot be read is only reported on
+/// standard error and leaves the status at zero.
+pub fn run(args: Args) -> i32 {
+    let Args {
+        options,
+        mut actions,
+    } = args;
+    if actions.is_empty() {
+        actions.push(Action::Print);
+    }
+    let contents_first = actions.iter().any(Action::is_delete);
+    let follow = options.follow();
+    if contents_first && follow == Follow::Always {
+        eprintln!("--delete cannot be used with -L");
+        return 1;
+    }
+
+    let names = options.name_globs();
+    let paths = options.path_globs();
+    let regexes = options.path_regexes();
+
+    let mut has_error = false;
+    let mut report = |e: MyError| {
+        eprintln!("{}", e);
+        has_error = true;
+    };
+    for path in &options.pathes {
+        let iter: Box<dyn Iterator<Item = MyResult<Entry>>> = match options.jobs {
+            None => Box::new(
+                PathIter::from(path)
+                    .contents_first(contents_first)
+                    .follow(follow)
+                    .same_file_system(options.same_file_system)
+                    .respect_ignore(options.respect_ignore)
+                    .sort(options.sort),
+            ),
+            Some(jobs) => Box::new(
+                ParallelIter::new(path, jobs)
+                    .sorted(!options.unordered)
+                    .contents_first(contents_first)
+                    .follow(follow)
+                    .same_file_system(options.same_file_system)
+                    .respect_ignore(options.respect_ignore)
+                    .sort(options.sort),
+            ),
+        };
+        let iter = iter
+            .filter(|x| is_match_names_or_err(x, &names))
+            .filter(|x| is_match_paths_or_err(x, &paths))
+            .filter(|x| is_match_regexes_or_err(x, &regexes))
+            .filter(|x| is_match_types_or_err(x, &options.types));
 
         iter.for_each(|x| match x {
             Err(e) => eprintln!("{}", e),
-            Ok(path) => println!("{}", path.display()),
+            Ok(entry) => {
+                for action in actions.iter_mut() {
+                    match action.apply(&entry) {
+                        Ok(true) => continue,
+                        Ok(false) => break,
+                        Err(e) => {
+                            report(e);
+                            break;
+                        }
+                    }
+                }
+            }
         });
     }
+    for action in actions.iter_mut() {
+        if let Err(e) = action.finish() {
+            report(e);
+        }
+    }
+    if has_error { 1 } else { 0 }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn terminate(args: &[&str]) -> Vec<OsString> {
+        terminate_exec_args(args.iter().map(OsString::from))
+    }
+
+    fn os_strings(args: &[&str]) -> Vec<OsString> {
+        args.iter().map(OsString::from).collect()
+    }
+
     #[test]
-    fn single_file() -> MyResult<()> {
-        let path = "tests/inputs/a/a.txt";
-        let result = PathIter::from(path)
-            .filter_map(|x| x.ok())
-            .collect::<Vec<_>>();
-        assert_eq!(result, vec![PathBuf::from(path)]);
-        Ok(())
+    fn terminate_exec_batch() {
+        let args = [
+            "findr",
+            "--exec",
+            "echo",
+            "{}",
+            "+",
+            ".",
+            "--execdir",
+            "ls",
+            "{}",
+            ";",
+        ];
+        let expected = [
+            "findr",
+            "--exec",
+            "echo",
+            "{}",
+            "+",
+            ";",
+            ".",
+            "--execdir",
+            "ls",
+            "{}",
+            ";",
+        ];
+        assert_eq!(terminate(&args), os_strings(&expected));
     }
 
     #[test]
-    fn one_depth_directory() -> MyResult<()> {
-        let path = "tests/inputs/f";
+    fn terminate_exec_batch_already_terminated() {
+        let args = ["findr", "--exec", "echo", "{}", "+", ";", "."];
+        assert_eq!(terminate(&args), os_strings(&args));
+    }
 
-        let expected = vec![
-            PathBuf::from("tests/inputs/f"),
-            PathBuf::from("tests/inputs/f/f.txt"),
-        ];
+    #[test]
+    fn terminate_exec_with_equals() {
+        let args = ["findr", "--exec=echo", "{}", "+", "."];
+        let expected = ["findr", "--exec", "echo", "{}", "+", ";", "."];
+        assert_eq!(terminate(&args), os_strings(&expected));
+    }
 
-        let actual = PathIter::from(path)
-            .filter_map(|x| x.ok())
+    #[test]
+    fn repeated_actions() {
+        let args = [
+            "findr", "--print", "--exec", "true", ";", "--print", "--printf", "%p",
+        ];
+        let args = Args::try_parse_from(args).unwrap();
+        let actions = args
+            .actions
+            .iter()
+            .map(|action| match action {
+                Action::Exec(_) => "exec",
+                Action::Delete => "delete",
+                Action::Print => "print",
+                Action::Print0 => "print0",
+                Action::Printf(_) => "printf",
+            })
             .collect::<Vec<_>>();
-
-        assert_eq!(actual, expected);
-        Ok(())
+        assert_eq!(actions, ["print", "exec", "print", "printf"]);
     }
 }
@@ -3,5 +3,5 @@ use findr::{self, Args};
 
 fn main() {
     let args = Args::parse();
-    findr::run(args);
+    std::process::exit(findr::run(args));
 }
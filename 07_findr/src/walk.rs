@@ -0,0 +1,537 @@
+use super::{MyError, MyResult, Type};
+use ignore::Match;
+use std::collections::VecDeque;
+use std::fs::{self, Metadata};
+use std::io;
+use std::os::unix::prelude::*;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+mod gitignore;
+use gitignore::Rules;
+
+mod parallel;
+pub use parallel::ParallelIter;
+
+mod sort;
+pub use sort::Sort;
+
+/// How symbolic links are treated, like find's `-P`, `-H` and `-L`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Follow {
+    /// Never follow symbolic links.
+    #[default]
+    Never,
+    /// Follow symbolic links given as starting points only.
+    CommandLine,
+    /// Follow all symbolic links.
+    Always,
+}
+
+impl Follow {
+    fn applies_to(self, depth: usize) -> bool {
+        match self {
+            Follow::Never => false,
+            Follow::CommandLine => depth == 0,
+            Follow::Always => true,
+        }
+    }
+}
+
+/// What the walkers do besides listing directories.
+#[derive(Debug, Clone, Copy, Default)]
+struct Settings {
+    follow: Follow,
+
+    /// Do not descend into directories on other devices than their parent, like find's `-xdev`.
+    same_file_system: bool,
+
+    /// Skip the paths matched by ignore files, like git does.
+    respect_ignore: bool,
+
+    /// The order of the entries of each directory.
+    sort: Sort,
+}
+
+/// A path found during traversal with its depth below the starting point.
+#[derive(Debug, Clone)]
+pub struct Entry {
+    path: PathBuf,
+    depth: usize,
+    metadata: Option<Metadata>,
+}
+
+impl Entry {
+    pub(crate) fn new(path: impl Into<PathBuf>, depth: usize) -> Entry {
+        Entry {
+            path: path.into(),
+            depth,
+            metadata: None,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// The metadata read during traversal, which describes the target of a followed link.
+    pub fn metadata(&self) -> Option<&Metadata> {
+        self.metadata.as_ref()
+    }
+
+    pub fn file_type(&self) -> Type {
+        match &self.metadata {
+            Some(metadata) => Type::from(metadata.file_type()),
+            None => Type::from(self.path()),
+        }
+    }
+
+    pub fn into_path(self) -> PathBuf {
+        self.path
+    }
+}
+
+/// A directory on the way from the starting point, used to detect file system loops and to
+/// apply the ignore files found on the way.
+struct Ancestor {
+    path: PathBuf,
+    id: (u64, u64),
+    rules: Option<Rules>,
+    parent: Option<Arc<Ancestor>>,
+}
+
+impl Ancestor {
+    fn find(self: &Arc<Ancestor>, id: (u64, u64)) -> Option<&Path> {
+        let mut ancestor = Some(self);
+        while let Some(a) = ancestor {
+            if a.id == id {
+                return Some(&a.path);
+            }
+            ancestor = a.parent.as_ref();
+        }
+        None
+    }
+
+    /// Whether `path`, found in this directory, is ignored. The ignore files of the nearest
+    /// directories take precedence, and `.git/info/exclude` comes after all of them.
+    fn is_ignored(self: &Arc<Ancestor>, path: &Path, is_dir: bool) -> bool {
+        for exclude in [false, true] {
+            let mut ancestor = Some(self);
+            while let Some(a) = ancestor {
+                match a.rules.as_ref().map(|r| r.matched(path, is_dir, exclude)) {
+                    Some(Match::Ignore(())) => return true,
+                    Some(Match::Whitelist(())) => return false,
+                    _ => ancestor = a.parent.as_ref(),
+                }
+            }
+        }
+        false
+    }
+}
+
+/// Reads the metadata of `path`, following a link when the mode says so. A dangling link is
+/// reported as the link itself, like find does.
+fn metadata(path: &Path, depth: usize, follow: Follow) -> io::Result<Metadata> {
+    let metadata = path.symlink_metadata()?;
+    if metadata.is_symlink() && follow.applies_to(depth) {
+        Ok(path.metadata().unwrap_or(metadata))
+    } else {
+        Ok(metadata)
+    }
+}
+
+/// Loads the metadata of `entry`. For a directory to descend into, also returns the ancestor
+/// chain its children are visited with, or an error if the directory is one of its own
+/// ancestors.
+fn visit(
+    mut entry: Entry,
+    parent: Option<Arc<Ancestor>>,
+    settings: Settings,
+) -> MyResult<(Entry, Option<Arc<Ancestor>>)> {
+    let metadata = metadata(&entry.path, entry.depth, settings.follow)
+        .map_err(|e| MyError::new(&entry.path, e))?;
+    let is_dir = metadata.is_dir();
+    let id = (metadata.dev(), metadata.ino());
+    entry.metadata = Some(metadata);
+    let other_device = parent.as_ref().is_some_and(|p| p.id.0 != id.0);
+    if !is_dir || (settings.same_file_system && other_device) {
+        return Ok((entry, None));
+    }
+
+    if let Some(loop_start) = parent.as_ref().and_then(|p| p.find(id)) {
+        let e = io::Error::other(format!(
+            "file system loop detected; it is the same directory as '{}'",
+            loop_start.display()
+        ));
+        return Err(MyError::new(&entry.path, e));
+    }
+    let ancestor = Arc::new(Ancestor {
+        path: entry.path.clone(),
+        id,
+        rules: settings
+            .respect_ignore
+            .then(|| Rules::load(&entry.path))
+            .flatten(),
+        parent,
+    });
+    Ok((entry, Some(ancestor)))
+}
+
+/// Lists the paths in the directory `dir` in the order of `sort`, along with the errors met
+/// while reading it. The ignored paths are left out with `respect_ignore`.
+fn read_dir(dir: &Arc<Ancestor>, settings: Settings) -> (Vec<PathBuf>, Vec<MyError>) {
+    let path = dir.path.as_path();
+    let mut errors = Vec::new();
+    let mut paths = match fs::read_dir(path) {
+        Err(e) => {
+            errors.push(MyError::new(path, e));
+            Vec::new()
+        }
+        Ok(iter) => iter
+            .filter_map(|x| match x {
+                Err(e) => {
+                    errors.push(MyError::new(path, e));
+                    None
+                }
+                Ok(v) => {
+                    // Like git, do not follow links to tell whether a path is a directory.
+                    let is_dir = v.file_type().is_ok_and(|t| t.is_dir());
+                    let path = v.path();
+                    let ignored = settings.respect_ignore && dir.is_ignored(&path, is_dir);
+                    (!ignored).then_some(path)
+                }
+            })
+            .collect(),
+    };
+    settings.sort.sort(&mut paths, settings.follow);
+    (paths, errors)
+}
+
+/// A path waiting to be visited. With `contents_first`, a directory is pushed back as `expanded`
+/// after its children, and is yielded when popped again.
+struct Pending {
+    entry: Entry,
+    expanded: bool,
+    parent: Option<Arc<Ancestor>>,
+}
+
+pub struct PathIter {
+    /// Errors encountered when traverse the file tree. If it's not empty, these errors
+    /// should be reported at first.
+    errors: VecDeque<MyResult<Entry>>,
+
+    to_be_visit: Vec<Pending>,
+
+    /// Yield the contents of a directory before the directory itself.
+    contents_first: bool,
+
+    settings: Settings,
+}
+
+impl PathIter {
+    pub fn contents_first(mut self, yes: bool) -> PathIter {
+        self.contents_first = yes;
+        self
+    }
+
+    pub fn follow(mut self, follow: Follow) -> PathIter {
+        self.settings.follow = follow;
+        self
+    }
+
+    /// Stay on the file system of the starting point, like find's `-xdev`.
+    pub fn same_file_system(mut self, yes: bool) -> PathIter {
+        self.settings.same_file_system = yes;
+        self
+    }
+
+    /// Skip the paths matched by the `.gitignore`, `.ignore` and `.git/info/exclude` files found
+    /// during traversal.
+    pub fn respect_ignore(mut self, yes: bool) -> PathIter {
+        self.settings.respect_ignore = yes;
+        self
+    }
+
+    /// Sort the entries of each directory as they are read.
+    pub fn sort(mut self, sort: Sort) -> PathIter {
+        self.settings.sort = sort;
+        self
+    }
+}
+
+impl<T> From<T> for PathIter
+where
+    T: AsRef<Path>,
+{
+    fn from(value: T) -> PathIter {
+        PathIter {
+            errors: VecDeque::new(),
+            to_be_visit: vec![Pending {
+                entry: Entry::new(value.as_ref(), 0),
+                expanded: false,
+                parent: None,
+            }],
+            contents_first: false,
+            settings: Settings::default(),
+        }
+    }
+}
+
+impl Iterator for PathIter {
+    type Item = MyResult<Entry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(next) = self.errors.pop_front() {
+                return Some(next);
+            }
+            let Pending {
+                entry,
+                expanded,
+                parent,
+            } = self.to_be_visit.pop()?;
+            if expanded {
+                return Some(Ok(entry));
+            }
+
+            let (entry, ancestor) = match visit(entry, parent, self.settings) {
+                Ok(visited) => visited,
+                Err(e) => return Some(Err(e)),
+            };
+            let Some(ancestor) = ancestor else {
+                return Some(Ok(entry));
+            };
+
+            let (children, errors) = read_dir(&ancestor, self.settings);
+            self.errors.extend(errors.into_iter().map(Err));
+            let depth = entry.depth + 1;
+            let entry = if self.contents_first {
+                self.to_be_visit.push(Pending {
+                    entry,
+                    expanded: true,
+                    parent: None,
+                });
+                None
+            } else {
+                Some(entry)
+            };
+            self.to_be_visit
+                .extend(children.into_iter().rev().map(|path| Pending {
+                    entry: Entry::new(path, depth),
+                    expanded: false,
+                    parent: Some(Arc::clone(&ancestor)),
+                }));
+            if let Some(entry) = entry {
+                return Some(Ok(entry));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn single_file() -> MyResult<()> {
+        let path = "tests/inputs/a/a.txt";
+        let result = PathIter::from(path)
+            .filter_map(|x| x.ok())
+            .map(Entry::into_path)
+            .collect::<Vec<_>>();
+        assert_eq!(result, vec![PathBuf::from(path)]);
+        Ok(())
+    }
+
+    #[test]
+    fn one_depth_directory() -> MyResult<()> {
+        let path = "tests/inputs/f";
+
+        let expected = vec![
+            PathBuf::from("tests/inputs/f"),
+            PathBuf::from("tests/inputs/f/f.txt"),
+        ];
+
+        let actual = PathIter::from(path)
+            .filter_map(|x| x.ok())
+            .map(Entry::into_path)
+            .collect::<Vec<_>>();
+
+        assert_eq!(actual, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn contents_first() -> MyResult<()> {
+        let path = "tests/inputs/a/b";
+
+        let actual = PathIter::from(path)
+            .contents_first(true)
+            .filter_map(|x| x.ok())
+            .collect::<Vec<_>>();
+
+        let mut found = actual
+            .iter()
+            .map(|x| (x.path().to_owned(), x.depth()))
+            .collect::<Vec<_>>();
+        found.sort();
+        let expected = vec![
+            (PathBuf::from("tests/inputs/a/b"), 0),
+            (PathBuf::from("tests/inputs/a/b/b.csv"), 1),
+            (PathBuf::from("tests/inputs/a/b/c"), 1),
+            (PathBuf::from("tests/inputs/a/b/c/c.mp3"), 2),
+        ];
+        assert_eq!(found, expected);
+
+        // The order of siblings depends on the file system, but every entry must come
+        // after all the entries below it.
+        for (i, entry) in actual.iter().enumerate() {
+            let below = actual
+                .iter()
+                .position(|x| x.path().starts_with(entry.path()));
+            assert_eq!(below.map(|j| j <= i), Some(true), "{:?}", entry.path());
+            assert!(
+                actual[i + 1..]
+                    .iter()
+                    .all(|x| !x.path().starts_with(entry.path())),
+                "{:?} comes before its contents",
+                entry.path()
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn sorted_by_name() {
+        let actual = PathIter::from("tests/inputs/d")
+            .sort(Sort::Name)
+            .filter_map(|x| x.ok())
+            .map(Entry::into_path)
+            .collect::<Vec<_>>();
+        let expected = [
+            "tests/inputs/d",
+            "tests/inputs/d/b.csv",
+            "tests/inputs/d/d.tsv",
+            "tests/inputs/d/d.txt",
+            "tests/inputs/d/e",
+            "tests/inputs/d/e/e.mp3",
+        ]
+        .map(PathBuf::from);
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn link_is_not_followed_by_default() {
+        let types = PathIter::from("tests/inputs/d/b.csv")
+            .filter_map(|x| x.ok())
+            .map(|x| x.file_type())
+            .collect::<Vec<_>>();
+        assert_eq!(types, vec![Type::Link]);
+    }
+
+    #[test]
+    fn link_is_followed() {
+        for follow in [Follow::CommandLine, Follow::Always] {
+            let types = PathIter::from("tests/inputs/d/b.csv")
+                .follow(follow)
+                .filter_map(|x| x.ok())
+                .map(|x| x.file_type())
+                .collect::<Vec<_>>();
+            assert_eq!(types, vec![Type::File]);
+        }
+    }
+
+    #[test]
+    fn same_file_system_in_one_device() -> io::Result<()> {
+        let tmp = TempDir::new()?;
+        fs::create_dir_all(tmp.path().join("a/b"))?;
+        fs::write(tmp.path().join("a/b/c.txt"), "")?;
+        let all = PathIter::from(tmp.path())
+            .filter_map(|x| x.ok())
+            .map(Entry::into_path)
+            .collect::<Vec<_>>();
+        let same = PathIter::from(tmp.path())
+            .same_file_system(true)
+            .filter_map(|x| x.ok())
+            .map(Entry::into_path)
+            .collect::<Vec<_>>();
+        assert_eq!(all.len(), 4);
+        assert_eq!(same, all);
+        Ok(())
+    }
+
+    #[test]
+    #[ignore = "walks the host /dev, whose mount points depend on the machine"]
+    fn same_file_system() {
+        let device = |path: &Path| path.symlink_metadata().map(|m| m.dev()).ok();
+        let root = Path::new("/dev");
+        let entries = PathIter::from(root)
+            .same_file_system(true)
+            .filter_map(|x| x.ok())
+            .collect::<Vec<_>>();
+        assert!(!entries.is_empty());
+        // Mount points are listed, but not their contents.
+        for entry in entries.iter().filter(|x| x.depth() > 0) {
+            let parent = entry.path().parent().unwrap();
+            assert_eq!(device(parent), device(root), "{:?}", entry.path());
+        }
+    }
+
+    fn make_loop() -> io::Result<TempDir> {
+        let tmp = TempDir::new()?;
+        fs::create_dir(tmp.path().join("a"))?;
+        std::os::unix::fs::symlink("..", tmp.path().join("a/up"))?;
+        Ok(tmp)
+    }
+
+    #[test]
+    fn link_to_dir() -> io::Result<()> {
+        let tmp = make_loop()?;
+        let up = tmp.path().join("a/up");
+
+        let never = PathIter::from(&up)
+            .filter_map(|x| x.ok())
+            .collect::<Vec<_>>();
+        assert_eq!(never.len(), 1);
+        assert_eq!(never[0].file_type(), Type::Link);
+
+        // Only the starting point is followed.
+        let command_line = PathIter::from(&up)
+            .follow(Follow::CommandLine)
+            .filter_map(|x| x.ok())
+            .map(|x| (x.depth, x.into_path()))
+            .collect::<Vec<_>>();
+        assert_eq!(command_line.len(), 3);
+        assert!(command_line.contains(&(2, up.join("a/up"))));
+        Ok(())
+    }
+
+    #[test]
+    fn loop_is_detected() -> io::Result<()> {
+        let tmp = make_loop()?;
+        let results = PathIter::from(tmp.path())
+            .follow(Follow::Always)
+            .collect::<Vec<_>>();
+
+        let paths = results
+            .iter()
+            .filter_map(|x| x.as_ref().ok())
+            .map(|x| x.path().to_owned())
+            .collect::<Vec<_>>();
+        assert_eq!(paths, vec![tmp.path().to_owned(), tmp.path().join("a")]);
+
+        let errors = results
+            .iter()
+            .filter_map(|x| x.as_ref().err())
+            .map(|e| e.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].contains("a/up: file system loop detected"));
+        Ok(())
+    }
+}
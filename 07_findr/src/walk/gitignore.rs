@@ -0,0 +1,48 @@
+use ignore::Match;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use std::path::Path;
+
+/// Files read in each directory, in increasing order of precedence.
+const IGNORE_FILES: [&str; 2] = [".gitignore", ".ignore"];
+
+/// Read in the directory containing `.git`, with a lower precedence than any ignore file.
+const EXCLUDE_FILE: &str = ".git/info/exclude";
+
+/// The ignore rules of one directory. Like git, invalid patterns and unreadable files are
+/// skipped.
+pub(super) struct Rules {
+    ignore: Gitignore,
+    exclude: Gitignore,
+}
+
+fn build(dir: &Path, files: &[&str]) -> Gitignore {
+    let mut builder = GitignoreBuilder::new(dir);
+    for file in files {
+        let path = dir.join(file);
+        if path.is_file() {
+            let _ = builder.add(path);
+        }
+    }
+    builder.build().unwrap_or_else(|_| Gitignore::empty())
+}
+
+impl Rules {
+    /// Reads the ignore files of `dir`. Returns `None` when there are none.
+    pub(super) fn load(dir: &Path) -> Option<Rules> {
+        let rules = Rules {
+            ignore: build(dir, &IGNORE_FILES),
+            exclude: build(dir, &[EXCLUDE_FILE]),
+        };
+        if rules.ignore.is_empty() && rules.exclude.is_empty() {
+            None
+        } else {
+            Some(rules)
+        }
+    }
+
+    /// Matches `path` against the ignore files, or the exclude file with `exclude`.
+    pub(super) fn matched(&self, path: &Path, is_dir: bool, exclude: bool) -> Match<()> {
+        let gitignore = if exclude { &self.exclude } else { &self.ignore };
+        gitignore.matched(path, is_dir).map(|_| ())
+    }
+}
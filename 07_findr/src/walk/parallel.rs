@@ -0,0 +1,472 @@
+use super::{Ancestor, Entry, Follow, Settings, Sort, read_dir, visit};
+use crate::{MyError, MyResult};
+use std::collections::VecDeque;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Condvar, Mutex, mpsc};
+use std::thread::{self, JoinHandle};
+
+/// The children of a directory, as read by a worker in sorted mode.
+struct Listing {
+    /// Errors met while reading the directory, reported before its children.
+    errors: Vec<MyError>,
+    children: Vec<Child>,
+}
+
+struct Child {
+    result: MyResult<Entry>,
+    /// Where the listing of the child will be put, if it is a directory to descend into.
+    slot: Option<Arc<Slot>>,
+}
+
+/// A listing filled in by a worker and awaited by the iterator.
+#[derive(Default)]
+struct Slot {
+    listing: Mutex<Option<Listing>>,
+    ready: Condvar,
+}
+
+impl Slot {
+    fn fill(&self, listing: Listing) {
+        *self.listing.lock().unwrap() = Some(listing);
+        self.ready.notify_one();
+    }
+
+    fn wait(&self) -> Listing {
+        let mut listing = self.listing.lock().unwrap();
+        loop {
+            if let Some(listing) = listing.take() {
+                return listing;
+            }
+            listing = self.ready.wait(listing).unwrap();
+        }
+    }
+}
+
+/// A directory waiting to be read by a worker.
+struct Job {
+    depth: usize,
+    ancestor: Arc<Ancestor>,
+    /// Where to put the listing in sorted mode. In unordered mode, entries are sent as found.
+    slot: Option<Arc<Slot>>,
+}
+
+#[derive(Default)]
+struct Queue {
+    jobs: Vec<Job>,
+    /// Number of jobs being processed, which may still push more jobs.
+    busy: usize,
+    stop: bool,
+}
+
+/// The state shared by the workers.
+struct Shared {
+    queue: Mutex<Queue>,
+    available: Condvar,
+    settings: Settings,
+}
+
+impl Shared {
+    fn push(&self, job: Job) {
+        self.queue.lock().unwrap().jobs.push(job);
+        self.available.notify_one();
+    }
+
+    /// Waits for a job. Returns `None` once every directory has been read or on `stop`.
+    fn pop(&self) -> Option<Job> {
+        let mut queue = self.queue.lock().unwrap();
+        loop {
+            if queue.stop {
+                return None;
+            }
+            if let Some(job) = queue.jobs.pop() {
+                queue.busy += 1;
+                return Some(job);
+            }
+            if queue.busy == 0 {
+                queue.stop = true;
+                self.available.notify_all();
+                return None;
+            }
+            queue = self.available.wait(queue).unwrap();
+        }
+    }
+
+    fn done(&self) {
+        let mut queue = self.queue.lock().unwrap();
+        queue.busy -= 1;
+        if queue.busy == 0 && queue.jobs.is_empty() {
+            self.available.notify_all();
+        }
+    }
+
+    fn stop(&self) {
+        self.queue.lock().unwrap().stop = true;
+        self.available.notify_all();
+    }
+
+    /// Visits `path` and queues it as a job when it is a directory to descend into.
+    fn visit(
+        &self,
+        path: PathBuf,
+        depth: usize,
+        parent: Option<Arc<Ancestor>>,
+        sorted: bool,
+    ) -> Child {
+        match visit(Entry::new(path, depth), parent, self.settings) {
+            Ok((entry, Some(ancestor))) => {
+                let slot = sorted.then(|| Arc::new(Slot::default()));
+                self.push(Job {
+                    depth: depth + 1,
+                    ancestor,
+                    slot: slot.clone(),
+                });
+                Child {
+                    result: Ok(entry),
+                    slot,
+                }
+            }
+            Ok((entry, None)) => Child {
+                result: Ok(entry),
+                slot: None,
+            },
+            Err(e) => Child {
+                result: Err(e),
+                slot: None,
+            },
+        }
+    }
+
+    fn work(&self, sender: mpsc::Sender<MyResult<Entry>>) {
+        while let Some(job) = self.pop() {
+            let (paths, errors) = read_dir(&job.ancestor, self.settings);
+            match &job.slot {
+                Some(slot) => {
+                    // Queue the children in reverse so the first ones are read first, as the
+                    // iterator needs them first.
+                    let mut children = paths
+                        .into_iter()
+                        .rev()
+                        .map(|path| {
+                            self.visit(path, job.depth, Some(Arc::clone(&job.ancestor)), true)
+                        })
+                        .collect::<Vec<_>>();
+                    children.reverse();
+                    slot.fill(Listing { errors, children });
+                }
+                None => {
+                    // A failed send means the iterator is gone; `stop` ends the work soon.
+                    for e in errors {
+                        let _ = sender.send(Err(e));
+                    }
+                    for path in paths {
+                        let child =
+                            self.visit(path, job.depth, Some(Arc::clone(&job.ancestor)), false);
+                        let _ = sender.send(child.result);
+                    }
+                }
+            }
+            self.done();
+        }
+    }
+}
+
+/// A path waiting to be yielded in sorted mode. With `contents_first`, a directory is pushed back
+/// as `expanded` after its children, and is yielded when popped again.
+struct Pending {
+    result: MyResult<Entry>,
+    slot: Option<Arc<Slot>>,
+    expanded: bool,
+}
+
+enum Output {
+    Sorted {
+        errors: VecDeque<MyError>,
+        to_be_visit: Vec<Pending>,
+    },
+    Unordered(mpsc::Receiver<MyResult<Entry>>),
+}
+
+struct Running {
+    shared: Arc<Shared>,
+    workers: Vec<JoinHandle<()>>,
+    output: Output,
+}
+
+/// Walks a file tree like [`PathIter`](super::PathIter), reading directories on several threads.
+///
+/// In the default sorted mode, the output is deterministic: the entries come depth-first, like
+/// `PathIter`, with the entries of each directory in the order of `sort`, or by name. Each
+/// directory is yielded once it has been read, but the workers do not wait for the iterator, so
+/// the listings they read ahead are held in memory until yielded. The unordered mode yields the
+/// entries as soon as they are found.
+pub struct ParallelIter {
+    root: PathBuf,
+    threads: NonZeroUsize,
+    sorted: bool,
+    contents_first: bool,
+    settings: Settings,
+    running: Option<Running>,
+}
+
+impl ParallelIter {
+    pub fn new(root: impl AsRef<Path>, threads: NonZeroUsize) -> ParallelIter {
+        ParallelIter {
+            root: root.as_ref().to_owned(),
+            threads,
+            sorted: true,
+            contents_first: false,
+            settings: Settings::default(),
+            running: None,
+        }
+    }
+
+    pub fn sorted(mut self, yes: bool) -> ParallelIter {
+        self.sorted = yes;
+        self
+    }
+
+    /// Yield the contents of a directory before the directory itself. This needs the order of
+    /// the sorted mode, so it overrides `sorted(false)`; findr rejects `--unordered` with
+    /// `--delete` for this reason.
+    pub fn contents_first(mut self, yes: bool) -> ParallelIter {
+        self.contents_first = yes;
+        self
+    }
+
+    pub fn follow(mut self, follow: Follow) -> ParallelIter {
+        self.settings.follow = follow;
+        self
+    }
+
+    pub fn same_file_system(mut self, yes: bool) -> ParallelIter {
+        self.settings.same_file_system = yes;
+        self
+    }
+
+    pub fn respect_ignore(mut self, yes: bool) -> ParallelIter {
+        self.settings.respect_ignore = yes;
+        self
+    }
+
+    /// The order of the entries of each directory in sorted mode, by name unless given.
+    pub fn sort(mut self, sort: Sort) -> ParallelIter {
+        self.settings.sort = sort;
+        self
+    }
+
+    fn start(&self) -> Running {
+        let sorted = self.sorted || self.contents_first;
+        let mut settings = self.settings;
+        if !sorted {
+            settings.sort = Sort::None;
+        } else if settings.sort == Sort::None {
+            settings.sort = Sort::Name;
+        }
+        let shared = Arc::new(Shared {
+            queue: Mutex::new(Queue::default()),
+            available: Condvar::new(),
+            settings,
+        });
+        let (sender, receiver) = mpsc::channel();
+
+        let root = shared.visit(self.root.clone(), 0, None, sorted);
+        let output = if sorted {
+            Output::Sorted {
+                errors: VecDeque::new(),
+                to_be_visit: vec![Pending {
+                    result: root.result,
+                    slot: root.slot,
+                    expanded: false,
+                }],
+            }
+        } else {
+            let _ = sender.send(root.result);
+            Output::Unordered(receiver)
+        };
+
+        let workers = (0..self.threads.get())
+            .map(|_| {
+                let shared = Arc::clone(&shared);
+                let sender = sender.clone();
+                thread::spawn(move || shared.work(sender))
+            })
+            .collect();
+        Running {
+            shared,
+            workers,
+            output,
+        }
+    }
+}
+
+impl Iterator for ParallelIter {
+    type Item = MyResult<Entry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.running.is_none() {
+            self.running = Some(self.start());
+        }
+        let contents_first = self.contents_first;
+        let running = self.running.as_mut()?;
+
+        let (errors, to_be_visit) = match &mut running.output {
+            Output::Unordered(receiver) => return receiver.recv().ok(),
+            Output::Sorted {
+                errors,
+                to_be_visit,
+            } => (errors, to_be_visit),
+        };
+        loop {
+            if let Some(e) = errors.pop_front() {
+                return Some(Err(e));
+            }
+            let Pending {
+                result,
+                slot,
+                expanded,
+            } = to_be_visit.pop()?;
+            let entry = match result {
+                Ok(entry) => entry,
+                Err(e) => return Some(Err(e)),
+            };
+            let Some(slot) = slot.filter(|_| !expanded) else {
+                return Some(Ok(entry));
+            };
+
+            let listing = slot.wait();
+            errors.extend(listing.errors);
+            let entry = if contents_first {
+                to_be_visit.push(Pending {
+                    result: Ok(entry),
+                    slot: None,
+                    expanded: true,
+                });
+                None
+            } else {
+                Some(entry)
+            };
+            to_be_visit.extend(listing.children.into_iter().rev().map(|child| Pending {
+                result: child.result,
+                slot: child.slot,
+                expanded: false,
+            }));
+            if let Some(entry) = entry {
+                return Some(Ok(entry));
+            }
+        }
+    }
+}
+
+impl Drop for ParallelIter {
+    fn drop(&mut self) {
+        if let Some(running) = self.running.take() {
+            running.shared.stop();
+            for worker in running.workers {
+                let _ = worker.join();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PathIter;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn threads(n: usize) -> NonZeroUsize {
+        NonZeroUsize::new(n).unwrap()
+    }
+
+    fn paths(iter: impl Iterator<Item = MyResult<Entry>>) -> Vec<PathBuf> {
+        iter.filter_map(|x| x.ok()).map(Entry::into_path).collect()
+    }
+
+    #[test]
+    fn sorted_is_deterministic() {
+        let expected = [
+            "tests/inputs",
+            "tests/inputs/a",
+            "tests/inputs/a/a.txt",
+            "tests/inputs/a/b",
+            "tests/inputs/a/b/b.csv",
+            "tests/inputs/a/b/c",
+            "tests/inputs/a/b/c/c.mp3",
+            "tests/inputs/d",
+            "tests/inputs/d/b.csv",
+            "tests/inputs/d/d.tsv",
+            "tests/inputs/d/d.txt",
+            "tests/inputs/d/e",
+            "tests/inputs/d/e/e.mp3",
+            "tests/inputs/f",
+            "tests/inputs/f/f.txt",
+            "tests/inputs/g.csv",
+        ]
+        .map(PathBuf::from);
+        for n in [1, 2, 8] {
+            assert_eq!(
+                paths(ParallelIter::new("tests/inputs", threads(n))),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn unordered_finds_the_same_entries() {
+        let mut expected = paths(PathIter::from("tests/inputs"));
+        expected.sort();
+        let mut actual = paths(ParallelIter::new("tests/inputs", threads(4)).sorted(false));
+        actual.sort();
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn contents_first() {
+        let actual = paths(ParallelIter::new("tests/inputs/a", threads(2)).contents_first(true));
+        let expected = [
+            "tests/inputs/a/a.txt",
+            "tests/inputs/a/b/b.csv",
+            "tests/inputs/a/b/c/c.mp3",
+            "tests/inputs/a/b/c",
+            "tests/inputs/a/b",
+            "tests/inputs/a",
+        ]
+        .map(PathBuf::from);
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn errors_are_reported() -> std::io::Result<()> {
+        let tmp = TempDir::new()?;
+        fs::create_dir(tmp.path().join("a"))?;
+        std::os::unix::fs::symlink("..", tmp.path().join("a/up"))?;
+
+        for sorted in [true, false] {
+            let results = ParallelIter::new(tmp.path(), threads(3))
+                .sorted(sorted)
+                .follow(Follow::Always)
+                .collect::<Vec<_>>();
+            let errors = results
+                .iter()
+                .filter_map(|x| x.as_ref().err())
+                .map(|e| e.to_string())
+                .collect::<Vec<_>>();
+            assert_eq!(errors.len(), 1);
+            assert!(errors[0].contains("a/up: file system loop detected"));
+            assert_eq!(results.len(), 3);
+        }
+
+        let missing = ParallelIter::new(tmp.path().join("missing"), threads(2)).collect::<Vec<_>>();
+        assert_eq!(missing.len(), 1);
+        assert!(missing[0].is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn stops_when_dropped() {
+        let mut iter = ParallelIter::new("tests/inputs", threads(4)).sorted(false);
+        assert!(iter.next().is_some());
+    }
+}
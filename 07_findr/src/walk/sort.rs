@@ -0,0 +1,112 @@
+use super::{Follow, metadata};
+use clap::ValueEnum;
+use std::cmp::{Ordering, Reverse};
+use std::os::unix::prelude::*;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// The order of the entries of a directory, like ls's `--sort`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Sort {
+    /// The order of the file system.
+    #[default]
+    None,
+    /// File names in byte order.
+    Name,
+    /// File names in natural order, with numbers compared by value.
+    Version,
+    /// Largest first.
+    Size,
+    /// Newest modification time first.
+    Mtime,
+}
+
+fn file_name(path: &Path) -> &[u8] {
+    path.file_name().map_or(&[], |name| name.as_bytes())
+}
+
+/// Splits `s` after its leading run of digits, or of non-digits.
+fn split_run(s: &[u8]) -> (&[u8], &[u8]) {
+    let is_digit = s[0].is_ascii_digit();
+    let end = s
+        .iter()
+        .position(|c| c.is_ascii_digit() != is_digit)
+        .unwrap_or(s.len());
+    s.split_at(end)
+}
+
+/// Compares names like `ls -v`: runs of digits by their value, and the rest byte by byte. Names
+/// only differing by leading zeros are ordered by bytes.
+fn version_cmp(a: &[u8], b: &[u8]) -> Ordering {
+    let (mut x, mut y) = (a, b);
+    while !x.is_empty() && !y.is_empty() {
+        let (run_x, rest_x) = split_run(x);
+        let (run_y, rest_y) = split_run(y);
+        let ordering = if run_x[0].is_ascii_digit() && run_y[0].is_ascii_digit() {
+            let trim = |run: &[u8]| -> usize { run.iter().take_while(|&&c| c == b'0').count() };
+            let (n, m) = (&run_x[trim(run_x)..], &run_y[trim(run_y)..]);
+            n.len().cmp(&m.len()).then_with(|| n.cmp(m))
+        } else {
+            run_x.cmp(run_y)
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+        (x, y) = (rest_x, rest_y);
+    }
+    x.len().cmp(&y.len()).then_with(|| a.cmp(b))
+}
+
+impl Sort {
+    /// Sorts the entries of a directory. Ties are broken by name, so the order is deterministic
+    /// for all orders but `None`.
+    pub(super) fn sort(self, paths: &mut [PathBuf], follow: Follow) {
+        // The entries of a directory are never starting points.
+        let metadata = |path: &Path| metadata(path, 1, follow).ok();
+        match self {
+            Sort::None => {}
+            Sort::Name => paths.sort_by(|a, b| file_name(a).cmp(file_name(b))),
+            Sort::Version => paths.sort_by(|a, b| version_cmp(file_name(a), file_name(b))),
+            Sort::Size => paths.sort_by_cached_key(|path| {
+                let size = metadata(path).map_or(0, |m| m.len());
+                (Reverse(size), file_name(path).to_vec())
+            }),
+            Sort::Mtime => paths.sort_by_cached_key(|path| {
+                let mtime = metadata(path).and_then(|m| m.modified().ok());
+                (
+                    Reverse(mtime.unwrap_or(SystemTime::UNIX_EPOCH)),
+                    file_name(path).to_vec(),
+                )
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_version_cmp() {
+        let mut names = ["a10", "a2", "a1", "b", "a02", "a", "a1b", "a1a"];
+        names.sort_by(|a, b| version_cmp(a.as_bytes(), b.as_bytes()));
+        assert_eq!(names, ["a", "a1", "a1a", "a1b", "a02", "a2", "a10", "b"]);
+    }
+
+    #[test]
+    fn test_version_cmp_versions() {
+        let mut names = ["v1.10.0", "v1.9.2", "v1.9.10", "v1.9"];
+        names.sort_by(|a, b| version_cmp(a.as_bytes(), b.as_bytes()));
+        assert_eq!(names, ["v1.9", "v1.9.2", "v1.9.10", "v1.10.0"]);
+    }
+
+    #[test]
+    fn test_name_is_byte_order() {
+        let mut paths = ["d/b", "d/B", "d/a10", "d/a2", "d/é"].map(PathBuf::from);
+        Sort::Name.sort(&mut paths, Follow::Never);
+        assert_eq!(
+            paths,
+            ["d/B", "d/a10", "d/a2", "d/b", "d/é"].map(PathBuf::from)
+        );
+    }
+}
@@ -3,6 +3,7 @@ use assert_cmd::Command;
 use predicates::prelude::*;
 // use pretty_assertions::assert_eq;
 use std::{borrow::Cow, fs, path::Path};
+use tempfile::TempDir;
 
 const PRG: &str = "findr";
 
@@ -23,10 +24,10 @@ fn skips_bad_dir() -> Result<()> {
 #[test]
 fn dies_bad_name() -> Result<()> {
     Command::cargo_bin(PRG)?
-        .args(["--name", "*.csv"])
+        .args(["--name", "[.csv"])
         .assert()
         .failure()
-        .stderr(predicate::str::contains("error: invalid value '*.csv'"));
+        .stderr(predicate::str::contains("error: invalid value '[.csv'"));
     Ok(())
 }
 
@@ -43,7 +44,7 @@ fn dies_bad_type() -> Result<()> {
 }
 
 // --------------------------------------------------
-fn format_file_name(expected_file: &str) -> Cow<str> {
+fn format_file_name(expected_file: &str) -> Cow<'_, str> {
     // Equivalent to: Cow::Borrowed(expected_file)
     expected_file.into()
 }
@@ -202,7 +203,7 @@ fn type_f_l() -> Result<()> {
 #[test]
 fn name_csv() -> Result<()> {
     run(
-        &["tests/inputs", "-n", ".*[.]csv"],
+        &["tests/inputs", "-n", "*.csv"],
         "tests/expected/name_csv.txt",
     )
 }
@@ -211,7 +212,7 @@ fn name_csv() -> Result<()> {
 #[test]
 fn name_csv_mp3() -> Result<()> {
     run(
-        &["tests/inputs", "-n", ".*[.]csv", "-n", ".*[.]mp3"],
+        &["tests/inputs", "-n", "*.csv", "-n", "*.mp3"],
         "tests/expected/name_csv_mp3.txt",
     )
 }
@@ -220,7 +221,7 @@ fn name_csv_mp3() -> Result<()> {
 #[test]
 fn name_txt_path_a_d() -> Result<()> {
     run(
-        &["tests/inputs/a", "tests/inputs/d", "--name", ".*.txt"],
+        &["tests/inputs/a", "tests/inputs/d", "--name", "*.txt"],
         "tests/expected/name_txt_path_a_d.txt",
     )
 }
@@ -228,14 +229,14 @@ fn name_txt_path_a_d() -> Result<()> {
 // --------------------------------------------------
 #[test]
 fn name_a() -> Result<()> {
-    run(&["tests/inputs", "-n", "a"], "tests/expected/name_a.txt")
+    run(&["tests/inputs", "-n", "a*"], "tests/expected/name_a.txt")
 }
 
 // --------------------------------------------------
 #[test]
 fn type_f_name_a() -> Result<()> {
     run(
-        &["tests/inputs", "-t", "f", "-n", "a"],
+        &["tests/inputs", "-t", "f", "-n", "a*"],
         "tests/expected/type_f_name_a.txt",
     )
 }
@@ -244,17 +245,500 @@ fn type_f_name_a() -> Result<()> {
 #[test]
 fn type_d_name_a() -> Result<()> {
     run(
-        &["tests/inputs", "--type", "d", "--name", "a"],
+        &["tests/inputs", "--type", "d", "--name", "a*"],
         "tests/expected/type_d_name_a.txt",
     )
 }
 
+// --------------------------------------------------
+#[test]
+fn iname_csv() -> Result<()> {
+    run(
+        &["tests/inputs", "--iname", "*.CSV"],
+        "tests/expected/name_csv.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn name_hidden() -> Result<()> {
+    let tmp = TempDir::new()?;
+    fs::write(tmp.path().join(".hidden"), "")?;
+    fs::write(tmp.path().join("shown"), "")?;
+    let shown = tmp.path().join("shown");
+    let hidden = tmp.path().join(".hidden");
+
+    Command::cargo_bin(PRG)?
+        .arg(tmp.path())
+        .args(["-t", "f", "-n", "*"])
+        .assert()
+        .success()
+        .stdout(format!("{}\n", shown.display()));
+    Command::cargo_bin(PRG)?
+        .arg(tmp.path())
+        .args(["-t", "f", "-n", ".*"])
+        .assert()
+        .success()
+        .stdout(format!("{}\n", hidden.display()));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn path_b() -> Result<()> {
+    run(
+        &["tests/inputs", "--path", "*/b/*"],
+        "tests/expected/path_glob_b.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn ipath_b() -> Result<()> {
+    run(
+        &["tests/inputs", "--ipath", "*/B/*"],
+        "tests/expected/path_glob_b.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn regex_csv() -> Result<()> {
+    run(
+        &["tests/inputs", "--regex", ".*/[a-z]+[.]csv"],
+        "tests/expected/name_csv.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn regex_is_anchored() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs", "--regex", "csv"])
+        .assert()
+        .success()
+        .stdout("");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn iregex_csv() -> Result<()> {
+    run(
+        &["tests/inputs", "--iregex", ".*/[A-Z]+[.]CSV"],
+        "tests/expected/name_csv.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn jobs_path1() -> Result<()> {
+    run(&["tests/inputs", "-j", "4"], "tests/expected/path1.txt")
+}
+
+// --------------------------------------------------
+#[test]
+fn jobs_unordered_path1() -> Result<()> {
+    run(
+        &["tests/inputs", "-j", "4", "--unordered"],
+        "tests/expected/path1.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn jobs_type_f_path_d_follow() -> Result<()> {
+    run(
+        &["tests/inputs/d", "-L", "-t", "f", "--jobs", "2"],
+        "tests/expected/type_f_path_d_follow.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn jobs_sorted_output() -> Result<()> {
+    let expected = fs::read_to_string("tests/expected/jobs_path_a.txt")?;
+    for jobs in ["1", "3"] {
+        Command::cargo_bin(PRG)?
+            .args(["tests/inputs/a", "-j", jobs])
+            .assert()
+            .success()
+            .stdout(expected.clone());
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_jobs() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs", "-j", "0"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("error: invalid value '0'"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_unordered_without_jobs() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs", "--unordered"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--jobs <N>"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_unordered_delete() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs", "-j", "2", "--unordered", "--delete"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "'--unordered' cannot be used with '--delete'",
+        ));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn respect_ignore() -> Result<()> {
+    let tmp = TempDir::new()?;
+    let root = tmp.path();
+    for dir in ["sub", "target", ".git/info"] {
+        fs::create_dir_all(root.join(dir))?;
+    }
+    let files = [
+        (".gitignore", "target/\n*.log\n!keep.log\n/only_root.txt\n"),
+        (".git/info/exclude", "excluded.txt\n"),
+        ("sub/.ignore", "!c.log\n"),
+        ("a.txt", ""),
+        ("b.log", ""),
+        ("keep.log", ""),
+        ("only_root.txt", ""),
+        ("excluded.txt", ""),
+        ("target/x", ""),
+        ("sub/c.log", ""),
+        ("sub/d.log", ""),
+        ("sub/only_root.txt", ""),
+        ("sub/target", ""),
+    ];
+    for (name, contents) in files {
+        fs::write(root.join(name), contents)?;
+    }
+
+    let expected = [
+        ".git/info/exclude",
+        ".gitignore",
+        "a.txt",
+        "keep.log",
+        "sub/.ignore",
+        "sub/c.log",
+        "sub/only_root.txt",
+        "sub/target",
+    ]
+    .map(|name| format!("{}\n", root.join(name).display()))
+    .concat();
+    for jobs in [None, Some("2")] {
+        let mut cmd = Command::cargo_bin(PRG)?;
+        cmd.arg(root).args(["-t", "f", "--respect-ignore"]);
+        if let Some(jobs) = jobs {
+            cmd.args(["-j", jobs]);
+        }
+        let out = cmd.assert().success().get_output().stdout.clone();
+        let mut lines = String::from_utf8(out)?
+            .lines()
+            .map(|line| format!("{line}\n"))
+            .collect::<Vec<_>>();
+        lines.sort();
+        assert_eq!(lines.concat(), expected);
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn xdev_path1() -> Result<()> {
+    for flag in ["--xdev", "--mount"] {
+        run(&["tests/inputs", flag], "tests/expected/path1.txt")?;
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn sort_name() -> Result<()> {
+    let expected = fs::read_to_string("tests/expected/sort_name.txt")?;
+    for args in [&["--sort", "name"][..], &["--sort=name", "-j", "2"]] {
+        Command::cargo_bin(PRG)?
+            .arg("tests/inputs")
+            .args(args)
+            .assert()
+            .success()
+            .stdout(expected.clone());
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn sort_version_size_mtime() -> Result<()> {
+    let tmp = TempDir::new()?;
+    let root = tmp.path();
+    let now = std::time::SystemTime::now();
+    // name, size, age in seconds
+    let files = [("f10", 1, 30), ("f9", 3, 10), ("F2", 2, 20)];
+    for (name, size, age) in files {
+        let file = fs::File::create(root.join(name))?;
+        file.set_len(size)?;
+        file.set_modified(now - std::time::Duration::from_secs(age))?;
+    }
+
+    let cases = [
+        ("name", ["F2", "f10", "f9"]),
+        ("version", ["F2", "f9", "f10"]),
+        ("size", ["f9", "F2", "f10"]),
+        ("mtime", ["f9", "F2", "f10"]),
+    ];
+    for (sort, names) in cases {
+        let expected = names
+            .map(|name| format!("{}\n", root.join(name).display()))
+            .concat();
+        Command::cargo_bin(PRG)?
+            .arg(root)
+            .args(["-t", "f", "--sort", sort])
+            .assert()
+            .success()
+            .stdout(expected);
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_sort() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs", "--sort", "foo"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("error: invalid value 'foo'"));
+    Ok(())
+}
+
 // --------------------------------------------------
 #[test]
 fn path_g() -> Result<()> {
     run(&["tests/inputs/g.csv"], "tests/expected/path_g.txt")
 }
 
+// --------------------------------------------------
+#[test]
+fn type_f_path_d_follow() -> Result<()> {
+    run(
+        &["-L", "tests/inputs/d", "--type", "f"],
+        "tests/expected/type_f_path_d_follow.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn type_l_follow() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["-L", "tests/inputs", "-t", "l"])
+        .assert()
+        .success()
+        .stdout("");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn type_l_follow_command_line() -> Result<()> {
+    run(
+        &["-H", "tests/inputs", "-t", "l"],
+        "tests/expected/type_l.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_delete_follow() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["-L", "tests/inputs", "--delete"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--delete cannot be used with -L"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn exec_type_f_path_a() -> Result<()> {
+    run(
+        &[
+            "tests/inputs/a",
+            "-t",
+            "f",
+            "--exec",
+            "echo",
+            "found",
+            "{}",
+            ";",
+        ],
+        "tests/expected/exec_type_f_path_a.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn exec_as_test() -> Result<()> {
+    run(
+        &[
+            "tests/inputs/d",
+            "--exec",
+            "test",
+            "-L",
+            "{}",
+            ";",
+            "--print",
+        ],
+        "tests/expected/exec_test_link_path_d.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn exec_batch_type_f_path_a() -> Result<()> {
+    let cmd = Command::cargo_bin(PRG)?
+        .args(["tests/inputs/a", "--exec", "echo", "{}", "+", "-t", "f"])
+        .assert()
+        .success();
+    let stdout = String::from_utf8(cmd.get_output().stdout.clone())?;
+
+    // All the paths are passed to a single command.
+    assert_eq!(stdout.lines().count(), 1);
+    let mut paths: Vec<&str> = stdout.split_whitespace().collect();
+    paths.sort();
+
+    let contents = fs::read_to_string("tests/expected/type_f_path_a.txt")?;
+    let mut expected: Vec<&str> = contents.lines().collect();
+    expected.sort();
+    assert_eq!(paths, expected);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn exec_batch_failure() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs/a", "--exec", "false", "{}", "+"])
+        .assert()
+        .failure()
+        .code(1);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_exec_batch_without_command() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs/a", "--exec", "{}", "+"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "error: --exec: missing command before \"{} +\"",
+        ));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn execdir_type_f_path_a() -> Result<()> {
+    run(
+        &["tests/inputs/a", "-t", "f", "--execdir", "echo", "{}", ";"],
+        "tests/expected/execdir_type_f_path_a.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn print_twice() -> Result<()> {
+    run(
+        &["tests/inputs/f", "--print", "--print"],
+        "tests/expected/print_print_path_f.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn print0_path_a() -> Result<()> {
+    let cmd = Command::cargo_bin(PRG)?
+        .args(["tests/inputs/a", "--print0"])
+        .assert()
+        .success();
+    let stdout = String::from_utf8(cmd.get_output().stdout.clone())?;
+
+    assert!(!stdout.contains('\n'));
+    assert!(stdout.ends_with('\0'));
+    let mut paths: Vec<&str> = stdout.split_terminator('\0').collect();
+    paths.sort();
+
+    let contents = fs::read_to_string("tests/expected/path_a.txt")?;
+    let mut expected: Vec<&str> = contents.lines().collect();
+    expected.sort();
+    assert_eq!(paths, expected);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn printf_path_a() -> Result<()> {
+    run(
+        &["tests/inputs/a", "--printf", "%d %f\\n"],
+        "tests/expected/printf_path_a.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn delete() -> Result<()> {
+    let tmp = TempDir::new()?;
+    let root = tmp.path().join("tree");
+    fs::create_dir_all(root.join("x/y"))?;
+    fs::write(root.join("x/y/z.txt"), "z")?;
+    fs::write(root.join("x/w.txt"), "w")?;
+
+    // A link out of the tree is removed, but never followed.
+    let outside = tmp.path().join("outside");
+    fs::create_dir(&outside)?;
+    fs::write(outside.join("keep.txt"), "keep")?;
+    std::os::unix::fs::symlink(&outside, root.join("link"))?;
+
+    let cmd = Command::cargo_bin(PRG)?
+        .arg(&root)
+        .args(["--delete", "--print"])
+        .assert()
+        .success();
+    let stdout = String::from_utf8(cmd.get_output().stdout.clone())?;
+    let lines: Vec<&str> = stdout.lines().collect();
+
+    assert_eq!(lines.len(), 6);
+    // Depth-first: every path is deleted before its parent directory.
+    for (i, line) in lines.iter().enumerate() {
+        let parent = Path::new(line).parent().unwrap().to_str().unwrap();
+        if let Some(j) = lines.iter().position(|l| *l == parent) {
+            assert!(i < j, "{line} is deleted after {parent}");
+        }
+    }
+    assert_eq!(lines.last(), root.to_str().as_ref());
+    assert!(!root.exists());
+    assert!(outside.join("keep.txt").exists());
+    Ok(())
+}
+
 // --------------------------------------------------
 #[test]
 fn unreadable_dir() -> Result<()> {
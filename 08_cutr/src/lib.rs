@@ -1,17 +1,30 @@
+use bstr::ByteSlice;
+use bstr::io::BufReadExt;
 use clap::{self, Parser};
 use map_ok::MapOk;
+use regex::bytes::Regex;
 use std::fs::File;
-use std::io::{self, BufRead, BufReader, Write};
-use std::path::PathBuf;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
 use thiserror::Error;
 
 mod range;
-use range::{RangeFilterEx, RangeList, parser};
+use range::{RangeFilterEx, RangeList, RangeSeq, parser};
 
 #[derive(Error, Debug)]
 pub enum MyError {
     #[error("{0}: {1}")]
     IOError(PathBuf, io::Error),
+
+    #[error("{0}: no column named '{1}' in the header")]
+    UnknownColumn(PathBuf, String),
+
+    #[error("the CSV delimiter must be a single byte: '{0}'")]
+    InvalidCsvDelimiter(String),
+
+    #[error("the delimiter must not be empty")]
+    EmptyDelimiter,
 }
 
 #[derive(Debug, Parser)]
@@ -20,14 +33,65 @@ pub struct Args {
     #[command(flatten)]
     selector: Selector,
 
+    /// use DELIM instead of TAB, or instead of comma with --csv, for field delimiter; DELIM
+    /// may be several characters long
     #[arg(
         long = "delimiter",
         short = 'd',
         value_name = "DELIM",
-        default_value_t = '\t',
         conflicts_with_all = ["bytes", "chars"],
     )]
-    delimiter: char,
+    delimiter: Option<String>,
+
+    /// split fields on runs of blanks, ignoring leading and trailing blanks
+    #[arg(
+        short = 'w',
+        long,
+        conflicts_with_all = ["bytes", "chars", "delimiter", "csv"],
+    )]
+    whitespace: bool,
+
+    /// split fields on the matches of the regular expression RE
+    #[arg(
+        long,
+        value_name = "RE",
+        value_parser = Regex::new,
+        conflicts_with_all = ["bytes", "chars", "delimiter", "whitespace", "csv"],
+    )]
+    regex_delimiter: Option<Regex>,
+
+    /// parse fields as CSV (RFC 4180), where quoted fields may contain delimiters, quotes and
+    /// newlines; fields are quoted on output as needed
+    #[arg(long, conflicts_with_all = ["bytes", "chars"])]
+    csv: bool,
+
+    /// with -b, do not split multibyte characters
+    #[arg(short = 'n', conflicts_with_all = ["chars", "fields", "names"])]
+    no_split: bool,
+
+    /// with -c, select grapheme clusters (user-perceived characters) instead of Unicode scalar
+    /// values
+    #[arg(short = 'g', long, conflicts_with_all = ["bytes", "fields", "names"])]
+    graphemes: bool,
+
+    /// complement the set of selected bytes, characters or fields
+    #[arg(long)]
+    complement: bool,
+
+    /// print the selected bytes, characters or fields in the order listed, repeating them as
+    /// often as they are listed
+    #[arg(long, conflicts_with = "complement")]
+    reorder: bool,
+
+    /// use STRING as the output delimiter; by default, the input delimiter for fields, a space
+    /// with -w, a TAB with --regex-delimiter and nothing between the ranges of bytes or
+    /// characters
+    #[arg(long, value_name = "STRING")]
+    output_delimiter: Option<String>,
+
+    /// do not print lines not containing delimiters
+    #[arg(short = 's', long, conflicts_with_all = ["bytes", "chars"])]
+    only_delimited: bool,
 
     #[arg(value_name = "FILE", default_value = "-")]
     files: Vec<PathBuf>,
@@ -43,7 +107,7 @@ pub struct Selector {
         value_name = "LIST",
         value_parser = parser::parse_range_list
     )]
-    bytes: Option<RangeList>,
+    bytes: Option<RangeSeq>,
 
     /// select only these characters
     #[arg(
@@ -52,7 +116,7 @@ pub struct Selector {
         value_name = "LIST",
         value_parser = parser::parse_range_list
     )]
-    chars: Option<RangeList>,
+    chars: Option<RangeSeq>,
 
     /// select only these fields
     #[arg(
@@ -61,38 +125,327 @@ pub struct Selector {
         value_name = "LIST",
         value_parser = parser::parse_range_list
     )]
-    fields: Option<RangeList>,
+    fields: Option<RangeSeq>,
+
+    /// select only the fields with these names in the first line
+    #[arg(
+        short = 'F',
+        long = "field-names",
+        value_name = "NAMES",
+        value_delimiter = ','
+    )]
+    names: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Unit {
+    Bytes,
+    Chars,
+    Graphemes,
+    Fields,
+}
+
+#[derive(Debug)]
+enum Columns {
+    Ranges(RangeSeq),
+    /// Names looked up in the first line of each file.
+    Names(Vec<String>),
 }
 
-fn select_bytes(line: &str, ranges: &RangeList) -> Vec<u8> {
-    line.as_bytes()
-        .iter()
-        .range_filter(ranges)
-        .copied()
-        .collect()
+/// How lines are split into fields.
+#[derive(Debug)]
+enum Delimiter {
+    Str(String),
+    /// Runs of blanks, leading and trailing blanks being ignored.
+    Whitespace,
+    Regex(Regex),
 }
 
-fn select_chars(line: &str, ranges: &RangeList) -> Vec<u8> {
-    line.chars()
-        .range_filter(ranges)
-        .collect::<String>()
-        .into_bytes()
+fn is_blank(c: &u8) -> bool {
+    *c == b' ' || *c == b'\t'
+}
+
+impl Delimiter {
+    /// The output delimiter when none is given.
+    fn output(&self) -> &str {
+        match self {
+            Delimiter::Str(delim) => delim,
+            Delimiter::Whitespace => " ",
+            Delimiter::Regex(_) => "\t",
+        }
+    }
+
+    /// Whether `line` has more than one field.
+    fn is_in(&self, line: &[u8]) -> bool {
+        self.split(line).nth(1).is_some()
+    }
+
+    fn split<'a>(&'a self, line: &'a [u8]) -> Box<dyn Iterator<Item = &'a [u8]> + 'a> {
+        match self {
+            Delimiter::Str(delim) => Box::new(line.split_str(delim)),
+            Delimiter::Whitespace => {
+                Box::new(line.split(is_blank).filter(|field| !field.is_empty()))
+            }
+            Delimiter::Regex(re) => Box::new(re.split(line)),
+        }
+    }
 }
 
-fn select_fields(line: &str, delim: char, ranges: &RangeList) -> Vec<u8> {
-    line.split(delim)
-        .range_filter(ranges)
-        .collect::<Vec<_>>()
-        .join(&delim.to_string())
-        .into_bytes()
+/// What to select in each line and how to print it.
+#[derive(Debug)]
+struct Selection {
+    unit: Unit,
+    columns: Columns,
+    complement: bool,
+    reorder: bool,
+    no_split: bool,
+    delimiter: Delimiter,
+    /// Always set for fields; for bytes and characters, printed between the ranges.
+    output_delimiter: Option<String>,
+    only_delimited: bool,
+    csv: bool,
 }
 
-fn select(line: &str, selector: &Selector, delimiter: char) -> Vec<u8> {
-    match (&selector.bytes, &selector.chars, &selector.fields) {
-        (Some(ranges), _, _) => select_bytes(line, ranges),
-        (_, Some(ranges), _) => select_chars(line, ranges),
-        (_, _, Some(ranges)) => select_fields(line, delimiter, ranges),
-        _ => unreachable!(),
+impl Args {
+    fn selection(&self) -> Result<Selection, MyError> {
+        let (unit, columns) = match &self.selector {
+            Selector {
+                bytes: Some(ranges),
+                ..
+            } => (Unit::Bytes, Columns::Ranges(ranges.clone())),
+            Selector {
+                chars: Some(ranges),
+                ..
+            } if self.graphemes => (Unit::Graphemes, Columns::Ranges(ranges.clone())),
+            Selector {
+                chars: Some(ranges),
+                ..
+            } => (Unit::Chars, Columns::Ranges(ranges.clone())),
+            Selector {
+                fields: Some(ranges),
+                ..
+            } => (Unit::Fields, Columns::Ranges(ranges.clone())),
+            Selector {
+                names: Some(names), ..
+            } => (Unit::Fields, Columns::Names(names.clone())),
+            _ => unreachable!(),
+        };
+        let delimiter = if self.whitespace {
+            Delimiter::Whitespace
+        } else if let Some(re) = &self.regex_delimiter {
+            Delimiter::Regex(re.clone())
+        } else {
+            match &self.delimiter {
+                Some(delim) if delim.is_empty() => return Err(MyError::EmptyDelimiter),
+                Some(delim) => Delimiter::Str(delim.clone()),
+                None => Delimiter::Str(if self.csv { "," } else { "\t" }.to_string()),
+            }
+        };
+        let output_delimiter = self
+            .output_delimiter
+            .clone()
+            .or_else(|| (unit == Unit::Fields).then(|| delimiter.output().to_string()));
+        if self.csv {
+            for delim in [
+                Some(delimiter.output().to_string()),
+                output_delimiter.clone(),
+            ] {
+                match delim {
+                    Some(delim) if delim.len() != 1 => {
+                        return Err(MyError::InvalidCsvDelimiter(delim));
+                    }
+                    _ => {}
+                }
+            }
+        }
+        Ok(Selection {
+            unit,
+            columns,
+            complement: self.complement,
+            reorder: self.reorder,
+            no_split: self.no_split,
+            delimiter,
+            output_delimiter,
+            only_delimited: self.only_delimited,
+            csv: self.csv,
+        })
+    }
+}
+
+/// The positions selected in a file: in input order like cut, or in the order listed with
+/// `--reorder`. Positions counted from the end are only known once a line is split.
+#[derive(Debug)]
+enum Positions {
+    InOrder(RangeList),
+    Listed(RangeSeq),
+    Relative {
+        seq: RangeSeq,
+        complement: bool,
+        reorder: bool,
+    },
+}
+
+impl Positions {
+    fn new(seq: RangeSeq, complement: bool, reorder: bool) -> Positions {
+        if seq.is_relative() {
+            Positions::Relative {
+                seq,
+                complement,
+                reorder,
+            }
+        } else if reorder {
+            Positions::Listed(seq)
+        } else if complement {
+            Positions::InOrder(RangeList::from(&seq).complement())
+        } else {
+            Positions::InOrder(RangeList::from(&seq))
+        }
+    }
+
+    /// Returns the selected parts of `items`: a part for each run of contiguous positions in
+    /// input order, or for each range listed.
+    fn slices<'a, T>(&self, items: &'a [T]) -> Vec<&'a [T]> {
+        match self {
+            Positions::InOrder(ranges) => ranges.slices(items),
+            Positions::Listed(seq) => seq.slices(items),
+            Positions::Relative {
+                seq,
+                complement,
+                reorder,
+            } => Positions::new(seq.resolve(items.len()), *complement, *reorder).slices(items),
+        }
+    }
+
+    /// Returns the positions in `line` with the ranges of bytes narrowed not to split its
+    /// multibyte characters.
+    fn aligned(&self, line: &[u8]) -> Positions {
+        match self {
+            Positions::InOrder(ranges) => Positions::InOrder(ranges.aligned(line)),
+            Positions::Listed(seq) => Positions::Listed(seq.aligned(line)),
+            Positions::Relative {
+                seq,
+                complement,
+                reorder,
+            } => Positions::new(seq.resolve(line.len()), *complement, *reorder).aligned(line),
+        }
+    }
+
+    /// Returns the selected items. In input order, `items` is filtered as it goes; in listed
+    /// order or with positions counted from the end, the items of the line are kept until they
+    /// are picked.
+    fn pick<T: Copy>(&self, items: impl Iterator<Item = T>) -> Vec<T> {
+        match self {
+            Positions::InOrder(ranges) => items.range_filter(ranges).collect(),
+            _ => self.slices(&items.collect::<Vec<_>>()).concat(),
+        }
+    }
+}
+
+impl Selection {
+    /// Returns the positions to select in the file `path`, whose first line is split into
+    /// `header`. An empty file has no header, and nothing to select either.
+    fn positions(&self, path: &Path, header: Option<Vec<&[u8]>>) -> Result<Positions, MyError> {
+        let seq = match (&self.columns, header) {
+            (Columns::Ranges(seq), _) => seq.clone(),
+            (Columns::Names(_), None) => RangeSeq::from_positions([]),
+            (Columns::Names(names), Some(header)) => {
+                let positions = names
+                    .iter()
+                    .map(
+                        |name| match header.iter().position(|x| *x == name.as_bytes()) {
+                            Some(i) => Ok(NonZeroUsize::MIN.saturating_add(i)),
+                            None => Err(MyError::UnknownColumn(path.to_owned(), name.clone())),
+                        },
+                    )
+                    .collect::<Result<Vec<_>, _>>()?;
+                RangeSeq::from_positions(positions)
+            }
+        };
+        Ok(Positions::new(seq, self.complement, self.reorder))
+    }
+}
+
+fn select_bytes(
+    line: &[u8],
+    positions: &Positions,
+    no_split: bool,
+    output_delimiter: Option<&str>,
+) -> Vec<u8> {
+    let aligned;
+    let positions = if no_split {
+        aligned = positions.aligned(line);
+        &aligned
+    } else {
+        positions
+    };
+    match output_delimiter {
+        Some(delim) => positions.slices(line).join(delim.as_bytes()),
+        None => positions.pick(line.iter().copied()),
+    }
+}
+
+/// Selects characters given as their bytes, so that invalid UTF-8 is kept as it is.
+fn select_chars<'a>(
+    chars: impl Iterator<Item = &'a [u8]>,
+    positions: &Positions,
+    output_delimiter: Option<&str>,
+) -> Vec<u8> {
+    match output_delimiter {
+        Some(delim) => positions
+            .slices(&chars.collect::<Vec<_>>())
+            .iter()
+            .map(|chars| chars.concat())
+            .collect::<Vec<_>>()
+            .join(delim.as_bytes()),
+        None => positions.pick(chars).concat(),
+    }
+}
+
+fn select_fields(
+    line: &[u8],
+    delim: &Delimiter,
+    positions: &Positions,
+    output_delimiter: &str,
+) -> Vec<u8> {
+    positions
+        .pick(delim.split(line))
+        .join(output_delimiter.as_bytes())
+}
+
+/// Returns the selected part of `line`, or `None` if the line is suppressed.
+fn select(line: &[u8], selection: &Selection, positions: &Positions) -> Option<Vec<u8>> {
+    let output_delimiter = selection.output_delimiter.as_deref();
+    match selection.unit {
+        Unit::Bytes => Some(select_bytes(
+            line,
+            positions,
+            selection.no_split,
+            output_delimiter,
+        )),
+        // An invalid UTF-8 sequence counts as a single character.
+        Unit::Chars => {
+            let chars = line.char_indices().map(|(start, end, _)| &line[start..end]);
+            Some(select_chars(chars, positions, output_delimiter))
+        }
+        Unit::Graphemes => {
+            let graphemes = line
+                .grapheme_indices()
+                .map(|(start, end, _)| &line[start..end]);
+            Some(select_chars(graphemes, positions, output_delimiter))
+        }
+        // Like cut, a line without delimiter is printed whole, unless it is suppressed.
+        Unit::Fields if !selection.delimiter.is_in(line) => {
+            (!selection.only_delimited).then(|| line.to_vec())
+        }
+        Unit::Fields => {
+            let output_delimiter = output_delimiter.unwrap_or_default();
+            Some(select_fields(
+                line,
+                &selection.delimiter,
+                positions,
+                output_delimiter,
+            ))
+        }
     }
 }
 
@@ -112,14 +465,150 @@ fn writeln(w: &mut impl Write, buf: &[u8]) -> Result<(), io::Error> {
     Ok(())
 }
 
+fn cut_lines(
+    path: &Path,
+    reader: Box<dyn BufRead>,
+    selection: &Selection,
+    out: &mut impl Write,
+) -> Result<(), MyError> {
+    let io_error = |e| MyError::IOError(path.to_owned(), e);
+    let mut lines = reader.byte_lines().peekable();
+    let header = match lines.peek() {
+        Some(Ok(line)) => Some(selection.delimiter.split(line).collect()),
+        _ => None,
+    };
+    let positions = selection.positions(path, header)?;
+    lines
+        .map_ok(|line| select(&line, selection, &positions))
+        .try_for_each(|v| match v? {
+            Some(v) => writeln(out, &v),
+            None => Ok(()),
+        })
+        .map_err(io_error)
+}
+
+/// Writes `fields` as a CSV record, quoting the fields that need it as RFC 4180 says.
+fn write_csv_record<'a>(
+    out: &mut impl Write,
+    fields: impl IntoIterator<Item = &'a [u8]>,
+    delim: u8,
+) -> Result<(), io::Error> {
+    let mut buf = Vec::new();
+    for (i, field) in fields.into_iter().enumerate() {
+        if i > 0 {
+            buf.push(delim);
+        }
+        if field
+            .iter()
+            .any(|&c| [delim, b'"', b'\n', b'\r'].contains(&c))
+        {
+            buf.push(b'"');
+            for &c in field {
+                if c == b'"' {
+                    buf.push(b'"');
+                }
+                buf.push(c);
+            }
+            buf.push(b'"');
+        } else {
+            buf.extend_from_slice(field);
+        }
+    }
+    writeln(out, &buf)
+}
+
+/// Reads CSV data, turning the blank lines outside quoted fields into empty quoted fields,
+/// which the CSV reader would otherwise skip.
+struct BlankLines<R> {
+    inner: R,
+    in_quotes: bool,
+    line: Vec<u8>,
+    pos: usize,
+}
+
+impl<R: BufRead> BlankLines<R> {
+    fn new(inner: R) -> BlankLines<R> {
+        BlankLines {
+            inner,
+            in_quotes: false,
+            line: Vec::new(),
+            pos: 0,
+        }
+    }
+}
+
+impl<R: BufRead> Read for BlankLines<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pos == self.line.len() {
+            self.line.clear();
+            self.pos = 0;
+            self.inner.read_until(b'\n', &mut self.line)?;
+            if !self.in_quotes && matches!(self.line.as_slice(), b"\n" | b"\r\n") {
+                self.line.splice(..0, *b"\"\"");
+            }
+            // An escaped quote counts twice, which leaves the state as it is.
+            let quotes = self.line.iter().filter(|&&c| c == b'"').count();
+            self.in_quotes ^= quotes % 2 == 1;
+        }
+        let n = buf.len().min(self.line.len() - self.pos);
+        buf[..n].copy_from_slice(&self.line[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+/// Cuts CSV records, which may span several lines. Like in line mode, a blank line is a record
+/// with a single empty field.
+fn cut_csv(
+    path: &Path,
+    reader: Box<dyn BufRead>,
+    selection: &Selection,
+    out: &mut impl Write,
+) -> Result<(), MyError> {
+    let io_error = |e| MyError::IOError(path.to_owned(), e);
+    // --csv conflicts with the other delimiters, and a CSV delimiter is a single byte.
+    let delimiter = selection.delimiter.output().as_bytes()[0];
+    let output_delimiter = selection
+        .output_delimiter
+        .as_ref()
+        .map_or(delimiter, |delim| delim.as_bytes()[0]);
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .delimiter(delimiter)
+        .from_reader(BlankLines::new(reader));
+
+    let mut records = reader.byte_records().peekable();
+    let header = match records.peek() {
+        Some(Ok(record)) => Some(record.iter().collect()),
+        _ => None,
+    };
+    let positions = selection.positions(path, header)?;
+    for record in records {
+        let record = record.map_err(|e| io_error(e.into()))?;
+        // Like a line without delimiter, a record with a single field is printed whole.
+        if record.len() == 1 {
+            if !selection.only_delimited {
+                write_csv_record(out, &record, output_delimiter).map_err(io_error)?;
+            }
+            continue;
+        }
+        let fields = positions.pick(record.iter());
+        write_csv_record(out, fields, output_delimiter).map_err(io_error)?;
+    }
+    Ok(())
+}
+
 pub fn run(args: Args) -> Result<(), MyError> {
+    let selection = args.selection()?;
     let mut stdout = io::stdout().lock();
     for path in args.files {
-        open_file(&path)?
-            .lines()
-            .map_ok(|line| select(&line, &args.selector, args.delimiter))
-            .try_for_each(|v| writeln(&mut stdout, &v?))
-            .map_err(|e| MyError::IOError(path.to_owned(), e))?;
+        let reader = open_file(&path)?;
+        if selection.csv {
+            cut_csv(&path, reader, &selection, &mut stdout)?;
+        } else {
+            cut_lines(&path, reader, &selection, &mut stdout)?;
+        }
     }
     Ok(())
 }
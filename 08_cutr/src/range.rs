@@ -6,11 +6,31 @@ pub mod parser;
 mod iter;
 pub use iter::RangeFilterEx;
 
+/// A position counted from the start of a line, or from its end with `~`, `~1` being the last.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Position {
+    Start(NonZeroUsize),
+    End(NonZeroUsize),
+}
+
+impl Position {
+    /// Returns the position counted from the start in a line of `len` items, or `None` if it is
+    /// before the first one.
+    fn resolve(self, len: usize) -> Option<usize> {
+        match self {
+            Position::Start(n) => Some(n.get()),
+            Position::End(n) => (len + 1).checked_sub(n.get()).filter(|&n| n > 0),
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Range {
     Between(NonZeroUsize, NonZeroUsize),
     To(NonZeroUsize),
     From(NonZeroUsize),
+    /// A range with an end-relative endpoint, resolved for each line.
+    Relative(Position, Position),
 }
 
 impl Range {
@@ -19,6 +39,7 @@ impl Range {
             Range::Between(start, _) => start.get(),
             Range::To(_) => 1,
             Range::From(start) => start.get(),
+            Range::Relative(..) => unreachable!("end-relative ranges are resolved first"),
         }
     }
 
@@ -27,10 +48,61 @@ impl Range {
             Range::Between(_, end) => end.get(),
             Range::To(end) => end.get(),
             Range::From(_) => usize::MAX,
+            Range::Relative(..) => unreachable!("end-relative ranges are resolved first"),
+        }
+    }
+
+    /// Returns the range in a line of `len` items, with positions counted from the start, or
+    /// `None` if it selects nothing.
+    fn resolve(&self, len: usize) -> Option<Range> {
+        let Range::Relative(start, end) = *self else {
+            return Some(*self);
+        };
+        let end = end.resolve(len)?;
+        // A start before the first item selects from the first one.
+        let start = start.resolve(len).unwrap_or(1);
+        (start <= end).then(|| Range::with_bounds(start, end))
+    }
+
+    /// Returns the range of bytes narrowed not to split the multibyte characters of `line`, like
+    /// POSIX `cut -n`: the start moves back to the first byte of its character, and the end back
+    /// to the last byte before the character it splits. Returns `None` if nothing is left.
+    fn aligned(&self, line: &[u8]) -> Option<Range> {
+        let mut start = self.start() - 1;
+        while start > 0 && start < line.len() && is_continuation(line[start]) {
+            start -= 1;
+        }
+        let mut end = self.end();
+        while end > 0 && end < line.len() && is_continuation(line[end]) {
+            end -= 1;
+        }
+        (start < end).then(|| Range::with_bounds(start + 1, end))
+    }
+
+    /// Returns the part of `items` in the range, or `None` if it is empty.
+    fn slice<'a, T>(&self, items: &'a [T]) -> Option<&'a [T]> {
+        let end = self.end().min(items.len());
+        (self.start() <= end).then(|| &items[self.start() - 1..end])
+    }
+
+    /// Builds the range from `start` to `end`, which are non-zero. An `end` of `usize::MAX`
+    /// means that the range is open-ended.
+    fn with_bounds(start: usize, end: usize) -> Range {
+        let start = NonZeroUsize::new(start).expect("start should be non-zero");
+        if end == usize::MAX {
+            Range::From(start)
+        } else {
+            let end = NonZeroUsize::new(end).expect("end should be non-zero");
+            Range::Between(start, end)
         }
     }
 }
 
+/// Whether `byte` is a continuation byte of a UTF-8 sequence, rather than its first byte.
+fn is_continuation(byte: u8) -> bool {
+    byte & 0xC0 == 0x80
+}
+
 impl Ord for Range {
     fn cmp(&self, other: &Range) -> Ordering {
         let left = (self.start(), self.end());
@@ -63,6 +135,131 @@ impl RangeList {
     fn iter(&self) -> std::slice::Iter<'_, Range> {
         self.0.iter()
     }
+
+    /// Returns the ranges of bytes narrowed not to split the multibyte characters of `line`.
+    pub fn aligned(&self, line: &[u8]) -> RangeList {
+        // Narrowing keeps the ranges in order.
+        RangeList(
+            self.0
+                .iter()
+                .filter_map(|range| range.aligned(line))
+                .collect(),
+        )
+    }
+
+    /// Merges the overlapping ranges, giving disjoint ranges in order. Like cut, adjacent ranges
+    /// are kept apart, so that the output delimiter goes between them.
+    fn merged(&self) -> Vec<Range> {
+        let mut bounds: Vec<(usize, usize)> = Vec::new();
+        for range in self.iter() {
+            match bounds.last_mut() {
+                Some((_, end)) if range.start() <= *end => {
+                    *end = (*end).max(range.end());
+                }
+                _ => bounds.push((range.start(), range.end())),
+            }
+        }
+        bounds
+            .into_iter()
+            .map(|(start, end)| Range::with_bounds(start, end))
+            .collect()
+    }
+
+    /// Returns the positions not in the list. An open-ended range leaves nothing after its
+    /// start, and the complement of a list ending before `usize::MAX` is open-ended.
+    pub fn complement(&self) -> RangeList {
+        let mut ranges = Vec::new();
+        // The first position not covered by the ranges seen so far.
+        let mut next = 1;
+        for range in self.merged() {
+            if range.start() > next {
+                ranges.push(Range::with_bounds(next, range.start() - 1));
+            }
+            if range.end() == usize::MAX {
+                return RangeList(ranges);
+            }
+            next = range.end() + 1;
+        }
+        ranges.push(Range::with_bounds(next, usize::MAX));
+        RangeList(ranges)
+    }
+
+    /// Returns the parts of `items` selected by the list, one for each run of contiguous
+    /// positions, leaving out the empty ones.
+    pub fn slices<'a, T>(&self, items: &'a [T]) -> Vec<&'a [T]> {
+        self.merged()
+            .iter()
+            .filter_map(|range| range.slice(items))
+            .collect()
+    }
+}
+
+/// Ranges in the order they are listed, which may overlap or repeat.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct RangeSeq(Vec<Range>);
+
+impl From<Vec<Range>> for RangeSeq {
+    fn from(ranges: Vec<Range>) -> RangeSeq {
+        RangeSeq(ranges)
+    }
+}
+
+impl From<&RangeSeq> for RangeList {
+    fn from(seq: &RangeSeq) -> RangeList {
+        RangeList::from(seq.0.clone())
+    }
+}
+
+impl RangeSeq {
+    #[cfg(test)]
+    fn iter(&self) -> std::slice::Iter<'_, Range> {
+        self.0.iter()
+    }
+
+    /// Whether some ranges are counted from the end, and must be resolved for each line.
+    pub fn is_relative(&self) -> bool {
+        self.0
+            .iter()
+            .any(|range| matches!(range, Range::Relative(..)))
+    }
+
+    /// Returns the ranges in a line of `len` items, with positions counted from the start.
+    pub fn resolve(&self, len: usize) -> RangeSeq {
+        RangeSeq(
+            self.0
+                .iter()
+                .filter_map(|range| range.resolve(len))
+                .collect(),
+        )
+    }
+
+    /// Returns the ranges of bytes narrowed not to split the multibyte characters of `line`.
+    pub fn aligned(&self, line: &[u8]) -> RangeSeq {
+        RangeSeq(
+            self.0
+                .iter()
+                .filter_map(|range| range.aligned(line))
+                .collect(),
+        )
+    }
+
+    /// Builds the sequence selecting each of `positions` in turn.
+    pub fn from_positions(positions: impl IntoIterator<Item = NonZeroUsize>) -> RangeSeq {
+        RangeSeq(
+            positions
+                .into_iter()
+                .map(|n| Range::Between(n, n))
+                .collect(),
+        )
+    }
+
+    /// Returns the parts of `items` selected by each range in turn, leaving out the empty ones.
+    pub fn slices<'a, T>(&self, items: &'a [T]) -> Vec<&'a [T]> {
+        self.0
+            .iter()
+            .filter_map(|range| range.slice(items))
+            .collect()
+    }
 }
 
 #[cfg(test)]
@@ -77,6 +274,20 @@ fn range_to(end: usize) -> Range {
     Range::To(end)
 }
 
+#[cfg(test)]
+fn range_relative(start: isize, end: isize) -> Range {
+    // Negative numbers stand for end-relative positions: -1 for `~1`.
+    let position = |n: isize| {
+        let abs = NonZeroUsize::new(n.unsigned_abs()).expect("position should be non-zero");
+        if n < 0 {
+            Position::End(abs)
+        } else {
+            Position::Start(abs)
+        }
+    };
+    Range::Relative(position(start), position(end))
+}
+
 #[cfg(test)]
 fn range_between(start: usize, end: usize) -> Range {
     let start = NonZeroUsize::new(start).expect("start shoudl be non-zero");
@@ -166,4 +377,132 @@ mod test {
         let range_list = RangeList::from(data);
         assert_eq!(range_list.0, expected);
     }
+
+    #[test]
+    fn merged() {
+        let range_list = RangeList::from([
+            range_between(2, 3),
+            range_between(4, 5),
+            range_between(3, 4),
+            range_between(8, 9),
+            range_from(12),
+            range_between(13, 14),
+        ]);
+        let expected = [range_between(2, 5), range_between(8, 9), range_from(12)];
+        assert_eq!(range_list.merged(), expected);
+
+        let range_list = RangeList::from([range_between(1, 2), range_between(3, 4)]);
+        let expected = [range_between(1, 2), range_between(3, 4)];
+        assert_eq!(range_list.merged(), expected);
+    }
+
+    #[test]
+    fn complement() {
+        let range_list = RangeList::from([range_between(3, 4), range_between(7, 7)]);
+        let expected = [range_between(1, 2), range_between(5, 6), range_from(8)];
+        assert_eq!(range_list.complement().0, expected);
+    }
+
+    #[test]
+    fn complement_open_ended() {
+        let range_list = RangeList::from([range_to(2), range_between(5, 6), range_from(9)]);
+        let expected = [range_between(3, 4), range_between(7, 8)];
+        assert_eq!(range_list.complement().0, expected);
+
+        assert_eq!(RangeList::from([range_from(1)]).complement().0, []);
+        assert_eq!(
+            RangeList::from([range_from(1)]).complement().complement().0,
+            [range_from(1)]
+        );
+    }
+
+    #[test]
+    fn complement_overlapping() {
+        let range_list = RangeList::from([range_to(5), range_between(2, 3), range_from(4)]);
+        assert_eq!(range_list.complement().0, []);
+    }
+
+    #[test]
+    fn slices() {
+        let range_list = RangeList::from([
+            range_between(2, 3),
+            range_between(4, 4),
+            range_between(6, 7),
+            range_from(10),
+        ]);
+        let items = [1, 2, 3, 4, 5, 6, 7, 8];
+        let expected: [&[i32]; 3] = [&[2, 3], &[4], &[6, 7]];
+        assert_eq!(range_list.slices(&items), expected);
+    }
+
+    #[test]
+    fn seq_slices() {
+        let seq = RangeSeq::from(vec![
+            range_between(6, 7),
+            range_between(2, 2),
+            range_between(2, 3),
+            range_from(7),
+            range_between(10, 12),
+        ]);
+        let items = [1, 2, 3, 4, 5, 6, 7, 8];
+        let expected: [&[i32]; 4] = [&[6, 7], &[2], &[2, 3], &[7, 8]];
+        assert_eq!(seq.slices(&items), expected);
+    }
+
+    #[test]
+    fn resolve() {
+        let seq = RangeSeq::from(vec![
+            range_relative(-1, -1),
+            range_relative(-3, -2),
+            range_relative(2, -2),
+            range_relative(1, -4),
+            range_relative(-9, -5),
+            range_between(2, 3),
+        ]);
+        let expected = [
+            range_between(5, 5),
+            range_between(3, 4),
+            range_between(2, 4),
+            range_between(1, 2),
+            range_between(1, 1),
+            range_between(2, 3),
+        ];
+        assert_eq!(seq.resolve(5).0, expected);
+
+        // Ranges past the start of a short line select nothing.
+        let expected = [range_between(1, 1), range_between(2, 3)];
+        assert_eq!(seq.resolve(1).0, expected);
+        assert_eq!(seq.resolve(0).0, [range_between(2, 3)]);
+    }
+
+    #[test]
+    fn aligned() {
+        // "aé€b": 'é' is bytes 2-3, '€' bytes 4-6.
+        let line = "aé€b".as_bytes();
+        let seq = RangeSeq::from(vec![
+            range_between(1, 2),
+            range_between(3, 5),
+            range_between(2, 6),
+            range_between(5, 5),
+            range_from(6),
+            range_to(3),
+            range_between(9, 12),
+        ]);
+        let expected = [
+            range_between(1, 1),
+            range_between(2, 3),
+            range_between(2, 6),
+            range_from(4),
+            range_between(1, 3),
+            range_between(9, 12),
+        ];
+        assert_eq!(seq.aligned(line).0, expected);
+    }
+
+    #[test]
+    fn seq_to_range_list() {
+        let seq = RangeSeq::from(vec![range_from(3), range_to(2), range_between(1, 1)]);
+        let expected = [range_between(1, 1), range_to(2), range_from(3)];
+        assert_eq!(RangeList::from(&seq).0, expected);
+    }
 }
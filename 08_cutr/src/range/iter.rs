@@ -8,7 +8,7 @@ pub struct RangeFilter<'a, I> {
 }
 
 impl<I> RangeFilter<'_, I> {
-    fn new(iter: I, range_list: &RangeList) -> RangeFilter<I> {
+    fn new(iter: I, range_list: &RangeList) -> RangeFilter<'_, I> {
         RangeFilter {
             iter,
             ranges: range_list.iter(),
@@ -55,13 +55,13 @@ impl<I: Iterator> Iterator for RangeFilter<'_, I> {
 }
 
 pub trait RangeFilterEx {
-    fn range_filter(self, range_list: &RangeList) -> RangeFilter<Self>
+    fn range_filter(self, range_list: &RangeList) -> RangeFilter<'_, Self>
     where
         Self: Sized;
 }
 
 impl<I: Iterator> RangeFilterEx for I {
-    fn range_filter(self, range_list: &RangeList) -> RangeFilter<Self> {
+    fn range_filter(self, range_list: &RangeList) -> RangeFilter<'_, Self> {
         RangeFilter::new(self, range_list)
     }
 }
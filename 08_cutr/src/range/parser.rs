@@ -1,4 +1,4 @@
-use super::{Range, RangeList};
+use super::{Position, Range, RangeSeq};
 use std::num::IntErrorKind;
 use std::num::NonZeroUsize;
 use thiserror::Error;
@@ -16,6 +16,12 @@ pub enum ParseError {
 
     #[error("decreasing range: '{0}'")]
     DecreasingRange(String),
+
+    #[error("missing position after '~': '{0}'")]
+    EmptyEndRelativePosition(String),
+
+    #[error("end-relative start with an absolute end: '{0}'")]
+    EndRelativeStartWithAbsoluteEnd(String),
 }
 
 fn parse_field(s: &str) -> Result<NonZeroUsize, ParseError> {
@@ -28,45 +34,65 @@ fn parse_field(s: &str) -> Result<NonZeroUsize, ParseError> {
     })
 }
 
+/// Parses a position, counted from the end when prefixed with `~`.
+fn parse_position(s: &str) -> Result<Position, ParseError> {
+    match s.strip_prefix('~') {
+        Some("") => Err(ParseError::EmptyEndRelativePosition(s.into())),
+        Some(field) => parse_field(field)
+            .map(Position::End)
+            .map_err(|_| ParseError::InvalidFieldValue(s.into())),
+        None => parse_field(s).map(Position::Start),
+    }
+}
+
 fn parse_range(s: &str) -> Result<Range, ParseError> {
+    let first = Position::Start(NonZeroUsize::MIN);
+    let last = Position::End(NonZeroUsize::MIN);
     match s.split_once('-') {
         Some(("", "")) => Err(ParseError::InvalidRangeWithNoEndpoint(s.into())),
-        Some((start, "")) => {
-            let start = parse_field(start)?;
-            Ok(Range::From(start))
-        }
-        Some(("", end)) => {
-            let end = parse_field(end)?;
-            Ok(Range::To(end))
-        }
-        Some((start, end)) => {
-            let start = parse_field(start)?;
-            let end = parse_field(end)?;
-            if start <= end {
+        Some((start, "")) => match parse_position(start)? {
+            Position::Start(start) => Ok(Range::From(start)),
+            start => Ok(Range::Relative(start, last)),
+        },
+        Some(("", end)) => match parse_position(end)? {
+            Position::Start(end) => Ok(Range::To(end)),
+            end => Ok(Range::Relative(first, end)),
+        },
+        Some((start, end)) => match (parse_position(start)?, parse_position(end)?) {
+            (Position::Start(start), Position::Start(end)) if start <= end => {
                 Ok(Range::Between(start, end))
-            } else {
-                Err(ParseError::DecreasingRange(s.into()))
             }
-        }
-        None => {
-            let field = parse_field(s)?;
-            Ok(Range::Between(field, field))
-        }
+            (Position::End(start), Position::End(end)) if start >= end => {
+                Ok(Range::Relative(Position::End(start), Position::End(end)))
+            }
+            (Position::Start(start), end @ Position::End(_)) => {
+                Ok(Range::Relative(Position::Start(start), end))
+            }
+            (Position::End(_), Position::Start(_)) => {
+                Err(ParseError::EndRelativeStartWithAbsoluteEnd(s.into()))
+            }
+            _ => Err(ParseError::DecreasingRange(s.into())),
+        },
+        None => match parse_position(s)? {
+            Position::Start(field) => Ok(Range::Between(field, field)),
+            field => Ok(Range::Relative(field, field)),
+        },
     }
 }
 
-pub fn parse_range_list(s: &str) -> Result<RangeList, ParseError> {
+/// Parses a list of ranges, keeping them in the order listed.
+pub fn parse_range_list(s: &str) -> Result<RangeSeq, ParseError> {
     let separators = [',', ' '];
     let ranges = s
         .split(separators)
         .map(parse_range)
         .collect::<Result<Vec<_>, _>>()?;
-    Ok(RangeList::from(ranges))
+    Ok(RangeSeq::from(ranges))
 }
 
 #[cfg(test)]
 mod tests {
-    use super::super::{range_between, range_from, range_to};
+    use super::super::{range_between, range_from, range_relative, range_to};
     use super::*;
 
     #[test]
@@ -153,7 +179,44 @@ mod tests {
         )
     }
 
-    fn assert_eq_range_list(actual: &RangeList, expected: &[Range]) {
+    #[test]
+    fn test_parse_range_end_relative() {
+        assert_eq!(parse_range("~1"), Ok(range_relative(-1, -1)));
+        assert_eq!(parse_range("~3-~1"), Ok(range_relative(-3, -1)));
+        assert_eq!(parse_range("~3-"), Ok(range_relative(-3, -1)));
+        assert_eq!(parse_range("-~2"), Ok(range_relative(1, -2)));
+        assert_eq!(parse_range("2-~2"), Ok(range_relative(2, -2)));
+    }
+
+    #[test]
+    fn test_parse_range_end_relative_invalid() {
+        assert_eq!(
+            parse_range("~"),
+            Err(ParseError::EmptyEndRelativePosition("~".into()))
+        );
+        assert_eq!(
+            parse_range("~-2"),
+            Err(ParseError::EmptyEndRelativePosition("~".into()))
+        );
+        assert_eq!(
+            parse_range("~0"),
+            Err(ParseError::InvalidFieldValue("~0".into()))
+        );
+        assert_eq!(
+            parse_range("~+1"),
+            Err(ParseError::InvalidFieldValue("~+1".into()))
+        );
+        assert_eq!(
+            parse_range("~1-~3"),
+            Err(ParseError::DecreasingRange("~1-~3".into()))
+        );
+        assert_eq!(
+            parse_range("~2-5"),
+            Err(ParseError::EndRelativeStartWithAbsoluteEnd("~2-5".into()))
+        );
+    }
+
+    fn assert_eq_range_list(actual: &RangeSeq, expected: &[Range]) {
         let actual = actual.iter().copied().collect::<Vec<_>>();
         assert_eq!(&actual, expected)
     }
@@ -190,6 +253,18 @@ mod tests {
         assert_eq_range_list(&actual, &expected);
     }
 
+    #[test]
+    fn test_parse_range_list_keeps_order() {
+        let input = "3,1-2,3";
+        let expected = [
+            range_between(3, 3),
+            range_between(1, 2),
+            range_between(3, 3),
+        ];
+        let actual = parse_range_list(input).unwrap();
+        assert_eq_range_list(&actual, &expected);
+    }
+
     #[test]
     fn test_parse_range_list_empty_string() {
         assert_eq!(parse_range_list(""), Err(ParseError::EmptyField));
@@ -9,6 +9,13 @@ const PRG: &str = "cutr";
 const CSV: &str = "tests/inputs/movies1.csv";
 const TSV: &str = "tests/inputs/movies1.tsv";
 const BOOKS: &str = "tests/inputs/books.tsv";
+const NODELIM: &str = "tests/inputs/nodelim.csv";
+const QUOTED: &str = "tests/inputs/quoted.csv";
+const BLANK_CSV: &str = "tests/inputs/blank.csv";
+const PS: &str = "tests/inputs/ps.txt";
+const COLONS: &str = "tests/inputs/colons.txt";
+const MIXED: &str = "tests/inputs/mixed.txt";
+const BINARY: &str = "tests/inputs/binary.txt";
 
 // --------------------------------------------------
 fn random_string() -> String {
@@ -59,7 +66,7 @@ fn dies_not_enough_args() -> Result<()> {
     dies(
         &[CSV],
         "the following required arguments were not provided:\n  \
-        <--bytes <LIST>|--characters <LIST>|--fields <LIST>>",
+        <--bytes <LIST>|--characters <LIST>|--fields <LIST>|--field-names <NAMES>>",
     )
 }
 
@@ -101,8 +108,7 @@ fn dies_bad_digit_chars() -> Result<()> {
 fn dies_empty_delimiter() -> Result<()> {
     dies(
         &[CSV, "-f", "1", "-d", ""],
-        // r#"--delim "" must be a single byte"#,
-        r#"invalid value '' for '--delimiter <DELIM>':"#,
+        "the delimiter must not be empty",
     )
 }
 
@@ -110,9 +116,8 @@ fn dies_empty_delimiter() -> Result<()> {
 #[test]
 fn dies_bad_delimiter() -> Result<()> {
     dies(
-        &[CSV, "-f", "1", "-d", ",,"],
-        // r#"--delim ",," must be a single byte"#,
-        r#"invalid value ',,' for '--delimiter <DELIM>':"#,
+        &[CSV, "--csv", "-f", "1", "-d", ",,"],
+        "the CSV delimiter must be a single byte: ',,'",
     )
 }
 
@@ -376,3 +381,440 @@ fn tsv_c1_8() -> Result<()> {
 fn repeated_value() -> Result<()> {
     run(&[BOOKS, "-c", "1,1"], "tests/expected/books.c1,1.out")
 }
+
+// --------------------------------------------------
+#[test]
+fn tsv_f2_complement() -> Result<()> {
+    run(
+        &[TSV, "-f", "2", "--complement"],
+        "tests/expected/movies1.tsv.f2.complement.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn tsv_f2_open_complement() -> Result<()> {
+    run(
+        &[TSV, "-f", "2-", "--complement"],
+        "tests/expected/movies1.tsv.f2-.complement.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn tsv_c_open_complement() -> Result<()> {
+    run(
+        &[TSV, "--characters=-2,4-", "--complement"],
+        "tests/expected/movies1.tsv.c-2,4-.complement.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn tsv_f1_3_output_delimiter() -> Result<()> {
+    run(
+        &[TSV, "-f", "1,3", "--output-delimiter", " | "],
+        "tests/expected/movies1.tsv.f1,3.outdelim.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn csv_f1_2_output_delimiter() -> Result<()> {
+    run(
+        &[CSV, "-f", "1-2", "-d", ",", "--output-delimiter", "\t"],
+        "tests/expected/movies1.csv.f1-2.dcomma.outdelim.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn tsv_b_output_delimiter() -> Result<()> {
+    run(
+        &[TSV, "-b", "1-3,5-6", "--output-delimiter=:"],
+        "tests/expected/movies1.tsv.b1-3,5-6.outdelim.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn tsv_c_adjacent_output_delimiter() -> Result<()> {
+    // Like cut, adjacent ranges are not merged, and are separated by the delimiter.
+    run(
+        &[TSV, "-c", "1-2,3-4,6", "--output-delimiter=:"],
+        "tests/expected/movies1.tsv.c1-2,3-4,6.outdelim.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn no_delimiter_is_printed() -> Result<()> {
+    run(
+        &[NODELIM, "-d", ",", "-f", "2"],
+        "tests/expected/nodelim.csv.f2.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn only_delimited() -> Result<()> {
+    run(
+        &[NODELIM, "-d", ",", "-f", "2", "-s"],
+        "tests/expected/nodelim.csv.f2.s.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_only_delimited_bytes() -> Result<()> {
+    dies(
+        &[CSV, "-b", "1", "--only-delimited"],
+        "the argument '--bytes <LIST>' cannot be used with '--only-delimited'",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn csv_quoted_f2() -> Result<()> {
+    run(
+        &[QUOTED, "--csv", "-f", "2"],
+        "tests/expected/quoted.csv.f2.csv.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn csv_quoted_f2_complement() -> Result<()> {
+    run(
+        &[QUOTED, "--csv", "-f", "2", "--complement"],
+        "tests/expected/quoted.csv.f2.csv.complement.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn csv_blank_line_f2() -> Result<()> {
+    // Like in line mode, a blank line is printed, but not a blank line in a quoted field.
+    run(
+        &[BLANK_CSV, "--csv", "-f", "2"],
+        "tests/expected/blank.csv.f2.csv.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn csv_blank_line_only_delimited() -> Result<()> {
+    run(
+        &[BLANK_CSV, "--csv", "-f", "1,3", "-s"],
+        "tests/expected/blank.csv.f1,3.csv.s.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn csv_quoted_output_delimiter() -> Result<()> {
+    run(
+        &[QUOTED, "--csv", "-f", "2-", "--output-delimiter", "\t"],
+        "tests/expected/quoted.csv.f2-.csv.outdelim.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn csv_quoted_field_names() -> Result<()> {
+    run(
+        &[QUOTED, "--csv", "-F", "notes,id"],
+        "tests/expected/quoted.csv.Fnotes,id.csv.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn csv_books_f3() -> Result<()> {
+    run(
+        &["tests/inputs/books.csv", "--csv", "-f", "3"],
+        "tests/expected/books.csv.f3.csv.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn tsv_field_names() -> Result<()> {
+    run(
+        &[TSV, "--field-names", "title,director"],
+        "tests/expected/movies1.tsv.Ftitle,director.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_unknown_field_name() -> Result<()> {
+    dies(
+        &[QUOTED, "--csv", "-F", "id,nope"],
+        "tests/inputs/quoted.csv: no column named 'nope' in the header",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_csv_multibyte_delimiter() -> Result<()> {
+    dies(
+        &[QUOTED, "--csv", "-f", "1", "--output-delimiter", "::"],
+        "the CSV delimiter must be a single byte: '::'",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_csv_bytes() -> Result<()> {
+    dies(
+        &[QUOTED, "--csv", "-b", "1"],
+        "the argument '--csv' cannot be used with '--bytes <LIST>'",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn tsv_f3_1_1_reorder() -> Result<()> {
+    run(
+        &[TSV, "-f", "3,1,1", "--reorder"],
+        "tests/expected/movies1.tsv.f3,1,1.reorder.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn tsv_b2_1_reorder() -> Result<()> {
+    run(
+        &[TSV, "-b", "2,1", "--reorder"],
+        "tests/expected/movies1.tsv.b2,1.reorder.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn books_c_reorder_output_delimiter() -> Result<()> {
+    run(
+        &[
+            BOOKS,
+            "-c",
+            "3,1-2,2-",
+            "--reorder",
+            "--output-delimiter",
+            "/",
+        ],
+        "tests/expected/books.c3,1-2,2-.reorder.outdelim.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn csv_field_names_reorder() -> Result<()> {
+    run(
+        &[QUOTED, "--csv", "-F", "notes,id,notes", "--reorder"],
+        "tests/expected/quoted.csv.Fnotes,id,notes.csv.reorder.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_reorder_complement() -> Result<()> {
+    dies(
+        &[TSV, "-f", "1", "--reorder", "--complement"],
+        "the argument '--reorder' cannot be used with '--complement'",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn tsv_f_last() -> Result<()> {
+    run(&[TSV, "-f", "~1"], "tests/expected/movies1.tsv.f~1.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn tsv_f_to_second_last() -> Result<()> {
+    run(
+        &[TSV, "--fields=-~2"],
+        "tests/expected/movies1.tsv.f-~2.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn tsv_f_to_last_complement() -> Result<()> {
+    run(
+        &[TSV, "-f", "2-~1", "--complement"],
+        "tests/expected/movies1.tsv.f2-~1.complement.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn tsv_f_last_first_reorder() -> Result<()> {
+    run(
+        &[TSV, "-f", "~1,1", "--reorder"],
+        "tests/expected/movies1.tsv.f~1,1.reorder.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn books_c_last_three() -> Result<()> {
+    run(
+        &[BOOKS, "-c", "~3-~1"],
+        "tests/expected/books.tsv.c~3-~1.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn books_b_from_second_last() -> Result<()> {
+    run(&[BOOKS, "-b", "~2-"], "tests/expected/books.tsv.b~2-.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_end_relative_start_absolute_end() -> Result<()> {
+    dies(
+        &[TSV, "-f", "~2-5"],
+        "end-relative start with an absolute end: '~2-5'",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_end_relative_empty() -> Result<()> {
+    dies(&[TSV, "-c", "~"], "missing position after '~': '~'")
+}
+
+// --------------------------------------------------
+#[test]
+fn ps_whitespace() -> Result<()> {
+    run(&[PS, "-w", "-f", "1,4"], "tests/expected/ps.txt.w.f1,4.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn ps_whitespace_only_delimited() -> Result<()> {
+    run(
+        &[PS, "-w", "-f", "2", "-s"],
+        "tests/expected/ps.txt.w.f2.s.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn ps_whitespace_reorder_output_delimiter() -> Result<()> {
+    run(
+        &[
+            PS,
+            "-w",
+            "-f",
+            "4,1",
+            "--reorder",
+            "--output-delimiter",
+            ",",
+        ],
+        "tests/expected/ps.txt.w.f4,1.reorder.outdelim.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn multi_char_delimiter() -> Result<()> {
+    run(
+        &[COLONS, "-d", "::", "-f", "2"],
+        "tests/expected/colons.txt.d::.f2.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn regex_delimiter() -> Result<()> {
+    run(
+        &[MIXED, "--regex-delimiter", r"\s*[,;]\s*", "-f", "1,3"],
+        "tests/expected/mixed.txt.regex.f1,3.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn regex_delimiter_open_ended() -> Result<()> {
+    run(
+        &[MIXED, "--regex-delimiter", r"\s*[,;]\s*", "-f", "2-"],
+        "tests/expected/mixed.txt.regex.f2-.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_invalid_regex_delimiter() -> Result<()> {
+    dies(
+        &[TSV, "--regex-delimiter", "(", "-f", "1"],
+        "invalid value '(' for '--regex-delimiter <RE>'",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_whitespace_delimiter() -> Result<()> {
+    dies(
+        &[TSV, "-w", "-d", ",", "-f", "1"],
+        "the argument '--whitespace' cannot be used with '--delimiter <DELIM>'",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn binary_b2() -> Result<()> {
+    run_lossy(&[BINARY, "-b", "2"], "tests/expected/binary.txt.b2.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn binary_b_no_split() -> Result<()> {
+    run_lossy(
+        &[BINARY, "-n", "-b", "1-2,4"],
+        "tests/expected/binary.txt.n.b1-2,4.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn binary_c2() -> Result<()> {
+    run_lossy(&[BINARY, "-c", "2"], "tests/expected/binary.txt.c2.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn binary_c2_graphemes() -> Result<()> {
+    run_lossy(
+        &[BINARY, "-g", "-c", "2"],
+        "tests/expected/binary.txt.g.c2.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn binary_f2() -> Result<()> {
+    run_lossy(&[BINARY, "-f", "2"], "tests/expected/binary.txt.f2.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_no_split_chars() -> Result<()> {
+    dies(
+        &[TSV, "-n", "-c", "1"],
+        "the argument '-n' cannot be used with '--characters <LIST>'",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_graphemes_fields() -> Result<()> {
+    dies(
+        &[TSV, "-g", "-f", "1"],
+        "the argument '--graphemes' cannot be used with '--fields <LIST>'",
+    )
+}
@@ -0,0 +1,144 @@
+use clap::ValueEnum;
+use std::borrow::Cow;
+use std::env;
+use std::io::{self, IsTerminal, Write};
+
+/// When to highlight the output.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum When {
+    /// Only when the standard output is a terminal.
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl When {
+    pub fn enabled(self) -> bool {
+        match self {
+            When::Auto => {
+                io::stdout().is_terminal() && env::var("TERM").is_ok_and(|term| term != "dumb")
+            }
+            When::Always => true,
+            When::Never => false,
+        }
+    }
+}
+
+/// The SGR sequences of the parts of the output, set with `GREP_COLORS` like GNU grep, for
+/// example `ms=01;31:fn=35:ne`. An empty sequence leaves the part as it is.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Colors {
+    pub selected_match: String,
+    pub context_match: String,
+    pub selected_line: String,
+    pub context_line: String,
+    pub filename: String,
+    pub line_number: String,
+    pub byte_offset: String,
+    pub separator: String,
+    /// Whether to clear to the end of the line after each sequence, which `ne` turns off.
+    erase: bool,
+}
+
+impl Default for Colors {
+    fn default() -> Colors {
+        Colors {
+            selected_match: "01;31".into(),
+            context_match: "01;31".into(),
+            selected_line: String::new(),
+            context_line: String::new(),
+            filename: "35".into(),
+            line_number: "32".into(),
+            byte_offset: "32".into(),
+            separator: "36".into(),
+            erase: true,
+        }
+    }
+}
+
+impl Colors {
+    /// Reads `GREP_COLORS` over the defaults.
+    pub fn from_env() -> Colors {
+        let mut colors = Colors::default();
+        if let Ok(spec) = env::var("GREP_COLORS") {
+            colors.update(&spec);
+        }
+        colors
+    }
+
+    /// Sets the capabilities listed in `spec`. Unknown and unsupported ones are ignored.
+    fn update(&mut self, spec: &str) {
+        for capability in spec.split(':') {
+            match capability.split_once('=') {
+                Some(("mt", sgr)) => {
+                    self.selected_match = sgr.into();
+                    self.context_match = sgr.into();
+                }
+                Some(("ms", sgr)) => self.selected_match = sgr.into(),
+                Some(("mc", sgr)) => self.context_match = sgr.into(),
+                Some(("sl", sgr)) => self.selected_line = sgr.into(),
+                Some(("cx", sgr)) => self.context_line = sgr.into(),
+                Some(("fn", sgr)) => self.filename = sgr.into(),
+                Some(("ln", sgr)) => self.line_number = sgr.into(),
+                Some(("bn", sgr)) => self.byte_offset = sgr.into(),
+                Some(("se", sgr)) => self.separator = sgr.into(),
+                None if capability == "ne" => self.erase = false,
+                _ => {}
+            }
+        }
+    }
+
+    /// Wraps `text` in the SGR sequence `sgr`.
+    pub fn paint<'a>(&self, sgr: &str, text: &'a str) -> Cow<'a, str> {
+        if sgr.is_empty() || text.is_empty() {
+            return Cow::Borrowed(text);
+        }
+        let erase = if self.erase { "\x1b[K" } else { "" };
+        Cow::Owned(format!("\x1b[{sgr}m{erase}{text}\x1b[m{erase}"))
+    }
+
+    /// Writes the bytes `text` wrapped in the SGR sequence `sgr`, like [`Colors::paint`].
+    pub fn write(&self, out: &mut impl Write, sgr: &str, text: &[u8]) -> io::Result<()> {
+        if sgr.is_empty() || text.is_empty() {
+            return out.write_all(text);
+        }
+        let erase = if self.erase { "\x1b[K" } else { "" };
+        write!(out, "\x1b[{sgr}m{erase}")?;
+        out.write_all(text)?;
+        write!(out, "\x1b[m{erase}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_update() {
+        let mut colors = Colors::default();
+        colors.update("mt=01;32:fn=:ne:xx=1:rv");
+        assert_eq!(colors.selected_match, "01;32");
+        assert_eq!(colors.context_match, "01;32");
+        assert_eq!(colors.filename, "");
+        assert_eq!(colors.line_number, "32");
+        assert!(!colors.erase);
+    }
+
+    #[test]
+    fn test_paint() {
+        let mut colors = Colors::default();
+        assert_eq!(colors.paint("35", "foo"), "\x1b[35m\x1b[Kfoo\x1b[m\x1b[K");
+        assert_eq!(colors.paint("", "foo"), "foo");
+        colors.update("ne");
+        assert_eq!(colors.paint("35", "foo"), "\x1b[35mfoo\x1b[m");
+    }
+
+    #[test]
+    fn test_write() {
+        let colors = Colors::default();
+        let mut out = Vec::new();
+        colors.write(&mut out, "35", b"\xfffoo").unwrap();
+        assert_eq!(out, b"\x1b[35m\x1b[K\xfffoo\x1b[m\x1b[K");
+    }
+}
@@ -0,0 +1,111 @@
+use super::{Kind, Line, Printer};
+use std::collections::VecDeque;
+use std::io::{self, Write};
+use std::path::Path;
+
+/// Where the lines are printed. Like GNU grep, groups of lines are separated across files too.
+pub struct Output<W> {
+    pub writer: W,
+    /// Whether a group of lines was printed, to separate the next one from.
+    grouped: bool,
+}
+
+impl<W: Write> Output<W> {
+    pub fn new(writer: W) -> Output<W> {
+        Output {
+            writer,
+            grouped: false,
+        }
+    }
+
+    /// Writes the output of a file searched on its own, separated from the groups before.
+    pub fn append(&mut self, printer: &Printer, other: Output<Vec<u8>>) -> io::Result<()> {
+        if other.grouped {
+            if self.grouped {
+                write_separator(printer, &mut self.writer)?;
+            }
+            self.grouped = true;
+        }
+        self.writer.write_all(&other.writer)
+    }
+}
+
+fn write_separator(printer: &Printer, out: &mut impl Write) -> io::Result<()> {
+    if let Some(separator) = printer.group_separator() {
+        let separator = printer.paint(|c| &c.separator, separator);
+        writeln!(out, "{}", separator)?;
+    }
+    Ok(())
+}
+
+/// Prints the selected lines of a file with their context. The lines before a selected line are
+/// kept in a ring buffer until it comes, and the lines after it are counted down.
+pub struct ContextWriter<'a, W> {
+    printer: &'a Printer,
+    output: &'a mut Output<W>,
+    path: Option<&'a Path>,
+    before: VecDeque<Line>,
+    after_left: usize,
+    /// The number of the last line printed.
+    last: Option<usize>,
+}
+
+impl<'a, W: Write> ContextWriter<'a, W> {
+    pub fn new(
+        printer: &'a Printer,
+        output: &'a mut Output<W>,
+        path: Option<&'a Path>,
+    ) -> ContextWriter<'a, W> {
+        ContextWriter {
+            printer,
+            output,
+            path,
+            before: VecDeque::with_capacity(printer.before_context),
+            after_left: 0,
+            last: None,
+        }
+    }
+
+    pub fn write(&mut self, line: Line, selected: bool) -> io::Result<()> {
+        if selected {
+            let first = self.before.front().map_or(line.number, |line| line.number);
+            self.separate(first)?;
+            while let Some(context) = self.before.pop_front() {
+                self.write_line(&context, Kind::Context)?;
+            }
+            self.write_line(&line, Kind::Selected)?;
+            self.after_left = self.printer.after_context;
+        } else if self.after_left > 0 {
+            self.write_line(&line, Kind::Context)?;
+            self.after_left -= 1;
+        } else if self.printer.before_context > 0 {
+            if self.before.len() == self.printer.before_context {
+                self.before.pop_front();
+            }
+            self.before.push_back(line);
+        }
+        Ok(())
+    }
+
+    /// Whether the lines to come are printed as the context after a selected line.
+    pub fn has_after_context(&self) -> bool {
+        self.after_left > 0
+    }
+
+    fn write_line(&mut self, line: &Line, kind: Kind) -> io::Result<()> {
+        self.last = Some(line.number);
+        self.printer
+            .write_line(&mut self.output.writer, self.path, line, kind)
+    }
+
+    /// Writes the group separator before a group starting at the line `first`, unless it follows
+    /// the last line printed.
+    fn separate(&mut self, first: usize) -> io::Result<()> {
+        let adjacent = self.last.is_some_and(|last| last + 1 == first);
+        if self.output.grouped && !adjacent {
+            write_separator(self.printer, &mut self.output.writer)?;
+        }
+        self.output.grouped = true;
+        Ok(())
+    }
+}
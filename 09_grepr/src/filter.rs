@@ -0,0 +1,139 @@
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use ignore::Match;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use std::path::Path;
+use walkdir::{self, DirEntry, WalkDir};
+
+/// Which files are searched, by the globs of `--include`, `--exclude` and `--exclude-dir`, and
+/// optionally by the `.gitignore` files found while walking.
+#[derive(Debug)]
+pub struct Filter {
+    include: Option<GlobSet>,
+    exclude: GlobSet,
+    exclude_dir: GlobSet,
+    gitignore: bool,
+}
+
+fn build(globs: &[String]) -> Result<GlobSet, globset::Error> {
+    let mut builder = GlobSetBuilder::new();
+    for glob in globs {
+        builder.add(Glob::new(glob)?);
+    }
+    builder.build()
+}
+
+/// Reads the `.gitignore` file of `dir`, if any. Like git, invalid patterns are skipped.
+fn load_gitignore(dir: &Path) -> Option<Gitignore> {
+    let path = dir.join(".gitignore");
+    if !path.is_file() {
+        return None;
+    }
+    let mut builder = GitignoreBuilder::new(dir);
+    let _ = builder.add(path);
+    builder.build().ok()
+}
+
+/// Whether `path` is ignored by the rules of its ancestors, the nearest ones first.
+fn is_ignored(rules: &[(usize, Gitignore)], path: &Path, is_dir: bool) -> bool {
+    for (_, gitignore) in rules.iter().rev() {
+        match gitignore.matched(path, is_dir) {
+            Match::Ignore(_) => return true,
+            Match::Whitelist(_) => return false,
+            Match::None => {}
+        }
+    }
+    false
+}
+
+impl Filter {
+    pub fn new(
+        include: &[String],
+        exclude: &[String],
+        exclude_dir: &[String],
+        gitignore: bool,
+    ) -> Result<Filter, globset::Error> {
+        Ok(Filter {
+            include: if include.is_empty() {
+                None
+            } else {
+                Some(build(include)?)
+            },
+            exclude: build(exclude)?,
+            exclude_dir: build(exclude_dir)?,
+            gitignore,
+        })
+    }
+
+    /// Whether the file `path` is searched, by its base name. Standard input always is.
+    pub fn is_included(&self, path: &Path) -> bool {
+        let Some(name) = path.file_name().filter(|_| path != Path::new("-")) else {
+            return true;
+        };
+        !self.exclude.is_match(name) && self.include.as_ref().is_none_or(|set| set.is_match(name))
+    }
+
+    /// Walks the tree under `root`, leaving out the excluded directories and, with `.gitignore`
+    /// files, the ignored entries and the `.git` directories. With `sorted`, the entries of each
+    /// directory come in the order of their names.
+    pub fn walk<'a>(
+        &'a self,
+        root: &Path,
+        sorted: bool,
+    ) -> impl Iterator<Item = walkdir::Result<DirEntry>> + 'a {
+        // The rules of the directories being walked, with their depth.
+        let mut rules: Vec<(usize, Gitignore)> = Vec::new();
+        let mut walker = WalkDir::new(root);
+        if sorted {
+            walker = walker.sort_by_file_name();
+        }
+        walker.into_iter().filter_entry(move |entry| {
+            let depth = entry.depth();
+            while rules.last().is_some_and(|(d, _)| *d >= depth) {
+                rules.pop();
+            }
+            let is_dir = entry.file_type().is_dir();
+            // Like GNU grep, the directories given as arguments are not excluded.
+            if is_dir && depth > 0 && self.exclude_dir.is_match(entry.file_name()) {
+                return false;
+            }
+            if self.gitignore {
+                if is_dir && depth > 0 && entry.file_name() == ".git" {
+                    return false;
+                }
+                if is_ignored(&rules, entry.path(), is_dir) {
+                    return false;
+                }
+                if is_dir && let Some(gitignore) = load_gitignore(entry.path()) {
+                    rules.push((depth, gitignore));
+                }
+            }
+            true
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn globs(globs: &[&str]) -> Vec<String> {
+        globs.iter().map(|glob| glob.to_string()).collect()
+    }
+
+    #[test]
+    fn test_is_included() {
+        let filter = Filter::new(&globs(&["*.rs", "*.txt"]), &globs(&["test*"]), &[], false);
+        let filter = filter.unwrap();
+        assert!(filter.is_included(Path::new("src/lib.rs")));
+        assert!(filter.is_included(Path::new("-")));
+        assert!(!filter.is_included(Path::new("src/test.rs")));
+        assert!(!filter.is_included(Path::new("Cargo.toml")));
+    }
+
+    #[test]
+    fn test_no_include() {
+        let filter = Filter::new(&[], &globs(&["*.o"]), &[], false).unwrap();
+        assert!(filter.is_included(Path::new("Cargo.toml")));
+        assert!(!filter.is_included(Path::new("target/main.o")));
+    }
+}
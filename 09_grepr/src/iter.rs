@@ -2,14 +2,22 @@ use std::io::{self, BufRead};
 
 pub struct Lines<B> {
     buf: B,
+    terminator: u8,
+}
+
+impl<B> Lines<B> {
+    /// Splits the input on `terminator`, rather than on newlines.
+    pub fn terminated_by(self, terminator: u8) -> Lines<B> {
+        Lines { terminator, ..self }
+    }
 }
 
 impl<B: BufRead> Iterator for Lines<B> {
-    type Item = io::Result<String>;
+    type Item = io::Result<Vec<u8>>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let mut buf = String::new();
-        match self.buf.read_line(&mut buf) {
+        let mut buf = Vec::new();
+        match self.buf.read_until(self.terminator, &mut buf) {
             Ok(0) => None,
             Ok(_) => Some(Ok(buf)),
             Err(e) => Some(Err(e)),
@@ -25,7 +33,10 @@ pub trait LinesNL {
 
 impl<B: BufRead> LinesNL for B {
     fn lines_nl(self) -> Lines<Self> {
-        Lines { buf: self }
+        Lines {
+            buf: self,
+            terminator: b'\n',
+        }
     }
 }
 
@@ -42,8 +53,20 @@ mod tests {
             foo bar\
         ",
         );
-        let expected = vec!["foo\n", "bar\r\n", "foo bar"];
+        let expected = vec![&b"foo\n"[..], b"bar\r\n", b"foo bar"];
         let actual = input.lines_nl().map(|v| v.unwrap()).collect::<Vec<_>>();
         assert_eq!(expected, actual);
     }
+
+    #[test]
+    fn test_terminated_by() {
+        let input = std::io::Cursor::new("foo\nbar\0baz");
+        let expected = vec![&b"foo\nbar\0"[..], b"baz"];
+        let actual = input
+            .lines_nl()
+            .terminated_by(0)
+            .map(|v| v.unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(expected, actual);
+    }
 }
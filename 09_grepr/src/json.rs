@@ -0,0 +1,178 @@
+use super::{Kind, Line, Stats};
+use base64::{Engine, engine::general_purpose::STANDARD};
+use serde::Serialize;
+use std::io::{self, Write};
+use std::path::Path;
+
+/// Bytes, as `{"text": ...}` when they are valid UTF-8, or else as `{"bytes": ...}` in base64.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Data<'a> {
+    Text(&'a str),
+    Bytes(String),
+}
+
+impl<'a> Data<'a> {
+    fn new(bytes: &'a [u8]) -> Data<'a> {
+        match std::str::from_utf8(bytes) {
+            Ok(text) => Data::Text(text),
+            Err(_) => Data::Bytes(STANDARD.encode(bytes)),
+        }
+    }
+
+    fn path(path: &'a Path) -> Data<'a> {
+        Data::new(path.as_os_str().as_encoded_bytes())
+    }
+}
+
+/// An event of the search, printed as `{"type": ..., "data": ...}`.
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", content = "data", rename_all = "lowercase")]
+enum Event<'a> {
+    Begin {
+        path: Data<'a>,
+    },
+    Match(LineData<'a>),
+    Context(LineData<'a>),
+    End {
+        path: Data<'a>,
+        binary: bool,
+        matched_lines: usize,
+        matches: usize,
+    },
+    Summary(&'a Stats),
+}
+
+#[derive(Debug, Serialize)]
+struct LineData<'a> {
+    path: Option<Data<'a>>,
+    /// The whole line, with its terminator.
+    lines: Data<'a>,
+    line_number: usize,
+    absolute_offset: usize,
+    submatches: Vec<Submatch<'a>>,
+}
+
+/// A match in a line, with offsets from the start of the line.
+#[derive(Debug, Serialize)]
+struct Submatch<'a> {
+    #[serde(rename = "match")]
+    matched: Data<'a>,
+    start: usize,
+    end: usize,
+    /// The capture groups that took part in the match.
+    groups: Vec<Group<'a>>,
+    /// The replacement of the match, with `--replace`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    replacement: Option<Data<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+struct Group<'a> {
+    index: usize,
+    name: Option<&'a str>,
+    #[serde(rename = "match")]
+    matched: Data<'a>,
+    start: usize,
+    end: usize,
+}
+
+fn write_event(out: &mut impl Write, event: &Event) -> io::Result<()> {
+    serde_json::to_writer(&mut *out, event)?;
+    writeln!(out)
+}
+
+pub fn write_begin(out: &mut impl Write, path: &Path) -> io::Result<()> {
+    let path = Data::path(path);
+    write_event(out, &Event::Begin { path })
+}
+
+/// Writes a selected line as a `match` event, or else as a `context` event. The names of the
+/// capture groups come from `group_names`, the first one being group 1.
+pub fn write_line(
+    out: &mut impl Write,
+    path: Option<&Path>,
+    line: &Line,
+    kind: Kind,
+    group_names: &[Option<String>],
+) -> io::Result<()> {
+    let submatches = line
+        .parts()
+        .enumerate()
+        .map(|(i, m)| {
+            let groups = line.captures.get(i).into_iter().flatten();
+            let groups = groups
+                .enumerate()
+                .filter_map(|(j, group)| {
+                    let group = group.as_ref()?;
+                    Some(Group {
+                        index: j + 1,
+                        name: group_names.get(j).and_then(|name| name.as_deref()),
+                        matched: Data::new(&line.text[group.clone()]),
+                        start: group.start,
+                        end: group.end,
+                    })
+                })
+                .collect();
+            Submatch {
+                matched: Data::new(&line.text[m.clone()]),
+                start: m.start,
+                end: m.end,
+                groups,
+                replacement: line
+                    .replacements
+                    .get(i)
+                    .map(|replacement| Data::new(replacement)),
+            }
+        })
+        .collect();
+    let data = LineData {
+        path: path.map(Data::path),
+        lines: Data::new(&line.text),
+        line_number: line.number,
+        absolute_offset: line.offset,
+        submatches,
+    };
+    let event = match kind {
+        Kind::Selected => Event::Match(data),
+        Kind::Context => Event::Context(data),
+    };
+    write_event(out, &event)
+}
+
+pub fn write_end(out: &mut impl Write, path: &Path, binary: bool, stats: &Stats) -> io::Result<()> {
+    let event = Event::End {
+        path: Data::path(path),
+        binary,
+        matched_lines: stats.matched_lines,
+        matches: stats.matches,
+    };
+    write_event(out, &event)
+}
+
+pub fn write_summary(out: &mut impl Write, stats: &Stats) -> io::Result<()> {
+    write_event(out, &Event::Summary(stats))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn to_json(event: &Event) -> String {
+        serde_json::to_string(event).unwrap()
+    }
+
+    #[test]
+    fn test_data() {
+        let path = Data::new(b"foo");
+        assert_eq!(
+            to_json(&Event::Begin { path }),
+            r#"{"type":"begin","data":{"path":{"text":"foo"}}}"#
+        );
+        let path = Data::new(b"caf\xe9");
+        assert_eq!(
+            to_json(&Event::Begin { path }),
+            r#"{"type":"begin","data":{"path":{"bytes":"Y2Fm6Q=="}}}"#
+        );
+    }
+}
@@ -1,14 +1,31 @@
-use clap::Parser;
+use bstr::ByteSlice;
+use clap::{ArgAction, Parser, ValueEnum};
 use itertools::Itertools;
-use regex::{self, Regex, RegexBuilder};
-use std::fs::File;
-use std::io::{self, BufRead, BufReader};
+use serde::Serialize;
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::num::NonZeroUsize;
+use std::ops::{AddAssign, Range};
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, mpsc};
+use std::thread;
 use thiserror::Error;
-use walkdir::{self, WalkDir};
 
+mod color;
+use color::{Colors, When};
+mod context;
+use context::{ContextWriter, Output};
+mod filter;
+use filter::Filter;
 mod iter;
 use iter::LinesNL;
+mod json;
+mod matcher;
+use matcher::Matcher;
 
 #[derive(Error, Debug)]
 #[error("{0}: {1}")]
@@ -25,11 +42,35 @@ impl From<walkdir::Error> for IOError {
 #[error("some errors")]
 pub struct MyError();
 
+/// How lines are printed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Encoding {
+    /// The bytes as they are read.
+    #[default]
+    Raw,
+    /// Invalid UTF-8 replaced with U+FFFD.
+    Lossy,
+}
+
+/// How to treat binary files, which have a NUL byte in their first block.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum BinaryFiles {
+    /// Print a one-line message instead of the selected lines.
+    #[default]
+    Binary,
+    /// Search them as text.
+    Text,
+    /// Assume that they do not match.
+    WithoutMatch,
+}
+
 #[derive(Parser, Debug)]
-#[command(version, about, long_about = None)]
+#[command(version, about, long_about = None, disable_help_flag = true)]
 pub struct Args {
-    /// Pattern in regular expression.
-    pattern: String,
+    /// Pattern in regular expression, unless patterns are given with -e or -f. Patterns are
+    /// separated by newlines.
+    #[arg(required_unless_present_any = ["regexp", "file"])]
+    pattern: Option<String>,
 
     /// Files to be searched for PATTERNS. "-" stands for standard input.
     /// If omitted, recursive searches examin the working directory,
@@ -37,6 +78,35 @@ pub struct Args {
     #[arg(value_name = "FILE")]
     files: Vec<PathBuf>,
 
+    /// Use PATTERNS for matching. Can be repeated.
+    #[arg(short = 'e', long, value_name = "PATTERNS")]
+    regexp: Vec<String>,
+
+    /// Take patterns from FILE, one per line. Can be repeated.
+    #[arg(short = 'f', long, value_name = "FILE")]
+    file: Vec<PathBuf>,
+
+    /// Match patterns as fixed strings, rather than regular expressions.
+    #[arg(short = 'F', long)]
+    fixed_strings: bool,
+
+    /// Select only matches that form whole words.
+    #[arg(short = 'w', long)]
+    word_regexp: bool,
+
+    /// Select only matches that form whole lines.
+    #[arg(short = 'x', long)]
+    line_regexp: bool,
+
+    /// Match patterns against whole files, so that they can span lines, and select every line
+    /// a match touches.
+    #[arg(short = 'U', long)]
+    multiline: bool,
+
+    /// Treat input and output lines as ending with NUL, rather than newline.
+    #[arg(short = 'z', long)]
+    null_data: bool,
+
     /// Ignore case distinction in patterns and input data.
     #[arg(short, long)]
     ignore_case: bool,
@@ -45,6 +115,44 @@ pub struct Args {
     #[arg(short, long)]
     recursive: bool,
 
+    /// Search the files of recursive searches with NUM threads.
+    #[arg(short = 'j', long, value_name = "NUM", default_value = "1")]
+    jobs: NonZeroUsize,
+
+    /// Print the files of recursive searches in the order of their paths, rather than as soon
+    /// as they are searched.
+    #[arg(long)]
+    sort_files: bool,
+
+    /// Search only the files whose base name matches GLOB. Can be repeated.
+    #[arg(long, value_name = "GLOB")]
+    include: Vec<String>,
+
+    /// Skip the files whose base name matches GLOB. Can be repeated.
+    #[arg(long, value_name = "GLOB")]
+    exclude: Vec<String>,
+
+    /// Skip the directories whose base name matches GLOB in recursive searches. Can be repeated.
+    #[arg(long, value_name = "GLOB")]
+    exclude_dir: Vec<String>,
+
+    /// Skip the files and directories ignored by .gitignore files, and .git directories, in
+    /// recursive searches.
+    #[arg(long)]
+    respect_gitignore: bool,
+
+    /// How to treat binary files.
+    #[arg(long, value_name = "TYPE", default_value = "binary")]
+    binary_files: BinaryFiles,
+
+    /// Search binary files as text, like --binary-files=text.
+    #[arg(short = 'a', long)]
+    text: bool,
+
+    /// How to print the lines, which may not be valid UTF-8.
+    #[arg(long, value_name = "ENCODING", default_value = "raw")]
+    encoding: Encoding,
+
     /// Invert the sense of matching, to select non-matchin lines.
     #[arg(short = 'v', long)]
     invert_match: bool,
@@ -53,10 +161,125 @@ pub struct Args {
     /// for each FILEs.
     #[arg(short, long)]
     count: bool,
+
+    /// Print only the names of files with selected lines.
+    #[arg(short = 'l', long, overrides_with = "files_without_match")]
+    files_with_matches: bool,
+
+    /// Print only the names of files without selected lines.
+    #[arg(short = 'L', long, overrides_with = "files_with_matches")]
+    files_without_match: bool,
+
+    /// Print nothing, and exit with zero status as soon as a line is selected, even if an error
+    /// was found.
+    #[arg(short, long, visible_alias = "silent")]
+    quiet: bool,
+
+    /// Suppress error messages about nonexistent or unreadable files.
+    #[arg(short = 's', long)]
+    no_messages: bool,
+
+    /// Stop reading a file after NUM selected lines, and their trailing context.
+    #[arg(short = 'm', long, value_name = "NUM")]
+    max_count: Option<usize>,
+
+    /// Prefix each line of output with its line number.
+    #[arg(short = 'n', long)]
+    line_number: bool,
+
+    /// Prefix each line of output with its byte offset in the file, or each part with -o.
+    #[arg(short, long)]
+    byte_offset: bool,
+
+    /// Print the file name for each match, the default when searching several files.
+    #[arg(short = 'H', long, overrides_with = "no_filename")]
+    with_filename: bool,
+
+    /// Never print file names.
+    #[arg(short = 'h', long, overrides_with = "with_filename")]
+    no_filename: bool,
+
+    /// Print only the matched, non-empty parts of lines, each on its own line.
+    #[arg(short, long)]
+    only_matching: bool,
+
+    /// Print NUM lines of trailing context after selected lines.
+    #[arg(short = 'A', long, value_name = "NUM")]
+    after_context: Option<usize>,
+
+    /// Print NUM lines of leading context before selected lines.
+    #[arg(short = 'B', long, value_name = "NUM")]
+    before_context: Option<usize>,
+
+    /// Print NUM lines of context before and after selected lines, unless -A or -B says
+    /// otherwise.
+    #[arg(short = 'C', long, value_name = "NUM")]
+    context: Option<usize>,
+
+    /// Print SEP between groups of lines with context, instead of "--".
+    #[arg(long, value_name = "SEP")]
+    group_separator: Option<String>,
+
+    /// Print nothing between groups of lines with context.
+    #[arg(long)]
+    no_group_separator: bool,
+
+    /// Highlight the matches, file names and separators, with colors from GREP_COLORS.
+    #[arg(
+        long,
+        visible_alias = "colour",
+        value_name = "WHEN",
+        default_value = "auto",
+        default_missing_value = "auto",
+        num_args = 0..=1,
+        require_equals = true
+    )]
+    color: When,
+
+    /// Print the selected lines with each match replaced by TEMPLATE, where $1 or ${name} stand
+    /// for a capture group, and $0 for the whole match.
+    #[arg(long, value_name = "TEMPLATE")]
+    replace: Option<String>,
+
+    /// Write the replacements to the files, each through a temporary file renamed over it, and
+    /// print the number of substitutions in each file instead.
+    #[arg(
+        long,
+        requires = "replace",
+        conflicts_with_all = [
+            "multiline",
+            "count",
+            "files_with_matches",
+            "files_without_match",
+            "quiet",
+            "json",
+        ]
+    )]
+    in_place: bool,
+
+    /// Keep a copy of each file edited in place, named with SUFFIX appended.
+    #[arg(long, value_name = "SUFFIX", requires = "in_place")]
+    backup_suffix: Option<String>,
+
+    /// Print the results as JSON Lines, with an event for the beginning and the end of each file,
+    /// each selected and context line, and a summary.
+    #[arg(long, conflicts_with_all = ["files_with_matches", "files_without_match"])]
+    json: bool,
+
+    /// Print help.
+    #[arg(long, action = ArgAction::Help)]
+    help: Option<bool>,
 }
 
 impl Args {
+    /// Takes the first positional argument as a file when patterns are given with -e or -f, and
+    /// sets the default paths if there are no files.
     pub fn set_default_paths_if_empty(mut self) -> Self {
+        if (!self.regexp.is_empty() || !self.file.is_empty())
+            && let Some(path) = self.pattern.take()
+        {
+            self.files.insert(0, path.into());
+        }
         if self.files.is_empty() {
             self.files = if self.recursive {
                 vec![".".into()]
@@ -67,10 +290,96 @@ impl Args {
         self
     }
 
-    fn build_regex(&self) -> Result<Regex, regex::Error> {
-        RegexBuilder::new(&self.pattern)
-            .case_insensitive(self.ignore_case)
-            .build()
+    /// Returns the patterns given as argument, with -e, and in the files given with -f.
+    fn patterns(&self) -> Result<Vec<String>, IOError> {
+        let mut patterns = self
+            .pattern
+            .iter()
+            .chain(&self.regexp)
+            .flat_map(|patterns| patterns.split('\n'))
+            .map(ToOwned::to_owned)
+            .collect::<Vec<_>>();
+        for path in &self.file {
+            for pattern in open(path)?.lines() {
+                patterns.push(pattern.map_err(|e| IOError(path.to_owned(), e))?);
+            }
+        }
+        Ok(patterns)
+    }
+
+    fn build_matcher(&self) -> Result<Matcher, Box<dyn std::error::Error>> {
+        let options = matcher::Options {
+            fixed_strings: self.fixed_strings,
+            ignore_case: self.ignore_case,
+            word: self.word_regexp,
+            line: self.line_regexp,
+            multiline: self.multiline,
+            captures: self.replace.is_some(),
+        };
+        Ok(Matcher::new(&self.patterns()?, options)?)
+    }
+
+    fn build_filter(&self) -> Result<Filter, globset::Error> {
+        Filter::new(
+            &self.include,
+            &self.exclude,
+            &self.exclude_dir,
+            self.respect_gitignore,
+        )
+    }
+
+    /// Whether to print file names, unless `-H` or `-h` says otherwise.
+    fn with_filename(&self, default: bool) -> bool {
+        self.with_filename || (default && !self.no_filename)
+    }
+
+    fn terminator(&self) -> u8 {
+        if self.null_data { b'\0' } else { b'\n' }
+    }
+
+    fn binary_files(&self) -> BinaryFiles {
+        if self.text {
+            BinaryFiles::Text
+        } else {
+            self.binary_files
+        }
+    }
+
+    fn mode(&self) -> Mode {
+        if self.in_place {
+            Mode::Edit
+        } else if self.quiet {
+            Mode::Quiet
+        } else if self.files_with_matches {
+            Mode::FilesWithMatches
+        } else if self.files_without_match {
+            Mode::FilesWithoutMatch
+        } else if self.count {
+            Mode::Count
+        } else {
+            Mode::Lines
+        }
+    }
+
+    fn printer(&self, matcher: &Matcher) -> Printer {
+        let after_context = self.after_context.or(self.context);
+        let before_context = self.before_context.or(self.context);
+        let has_context = after_context.is_some() || before_context.is_some();
+        let group_separator = self.group_separator.as_deref().unwrap_or("--");
+        Printer {
+            line_number: self.line_number,
+            byte_offset: self.byte_offset,
+            only_matching: self.only_matching,
+            terminator: self.terminator(),
+            after_context: after_context.unwrap_or_default(),
+            before_context: before_context.unwrap_or_default(),
+            group_separator: (has_context && !self.no_group_separator && !self.json)
+                .then(|| group_separator.to_string()),
+            colors: self.color.enabled().then(Colors::from_env),
+            lossy: self.encoding == Encoding::Lossy,
+            json: self.json && !self.quiet,
+            group_names: matcher.group_names(),
+        }
     }
 }
 
@@ -85,72 +394,748 @@ fn open<T: AsRef<Path>>(path: T) -> Result<Box<dyn BufRead>, IOError> {
     }
 }
 
-fn print_line<I>(mut iter: I, path: &Path, should_print_path: bool) -> io::Result<()>
-where
-    I: Iterator<Item = io::Result<String>>,
-{
-    iter.try_for_each(|line| {
-        let line = line?;
-        if should_print_path {
-            print!("{}:", path.to_string_lossy());
+/// A line of input, with the positions of the matches in it. Its bytes may not be valid UTF-8.
+struct Line {
+    number: usize,
+    /// The offset of the line in bytes from the start of the input.
+    offset: usize,
+    text: Vec<u8>,
+    /// The length of `text` without its line terminator.
+    len: usize,
+    /// The positions of the matches in the line, without its terminator.
+    matches: Vec<Range<usize>>,
+    /// The capture groups of each part, filled in for JSON output.
+    captures: Vec<Vec<Option<Range<usize>>>>,
+    /// The replacement of each part, filled in for selected lines with `--replace`.
+    replacements: Vec<Vec<u8>>,
+}
+
+impl Line {
+    /// A line ending with `terminator`, or with "\r\n" for newlines, yet to be matched.
+    fn new(number: usize, offset: usize, text: Vec<u8>, terminator: u8) -> Line {
+        let content = text.strip_suffix(&[terminator]).unwrap_or(&text);
+        let content = match terminator {
+            b'\n' => content.strip_suffix(b"\r").unwrap_or(content),
+            _ => content,
+        };
+        let len = content.len();
+        Line {
+            number,
+            offset,
+            text,
+            len,
+            matches: Vec::new(),
+            captures: Vec::new(),
+            replacements: Vec::new(),
+        }
+    }
+
+    fn content(&self) -> &[u8] {
+        &self.text[..self.len]
+    }
+
+    /// The matches to print, as empty matches have nothing to show.
+    fn parts(&self) -> impl Iterator<Item = &Range<usize>> {
+        self.matches.iter().filter(|m| !m.is_empty())
+    }
+
+    /// The text of the `i`th part, or its replacement if any.
+    fn part(&self, i: usize, m: &Range<usize>) -> &[u8] {
+        self.replacements
+            .get(i)
+            .map_or(&self.text[m.clone()], |replacement| replacement)
+    }
+
+    /// The line with its parts replaced.
+    fn replaced(&self) -> Vec<u8> {
+        let mut text = Vec::with_capacity(self.text.len());
+        let mut last = 0;
+        for (i, m) in self.parts().enumerate() {
+            text.extend_from_slice(&self.text[last..m.start]);
+            text.extend_from_slice(self.part(i, m));
+            last = m.end;
+        }
+        text.extend_from_slice(&self.text[last..]);
+        text
+    }
+}
+
+/// Reads the lines of `reader`, ending with `terminator`, matched against `matcher` one by one,
+/// or all at once in the whole input with `multiline`.
+fn lines<'a>(
+    mut reader: Box<dyn BufRead>,
+    matcher: &'a Matcher,
+    terminator: u8,
+    multiline: bool,
+) -> Box<dyn Iterator<Item = io::Result<Line>> + 'a> {
+    if multiline {
+        let mut buf = Vec::new();
+        if let Err(e) = reader.read_to_end(&mut buf) {
+            return Box::new(std::iter::once(Err(e)));
+        }
+        let matches = matcher.find_iter(&buf);
+        return Box::new(
+            split_matches(&buf, &matches, terminator)
+                .into_iter()
+                .map(Ok),
+        );
+    }
+    let mut offset = 0;
+    let lines = reader.lines_nl().terminated_by(terminator).enumerate();
+    Box::new(lines.map(move |(i, text)| {
+        let mut line = Line::new(i + 1, offset, text?, terminator);
+        line.matches = matcher.find_iter(line.content());
+        offset += line.text.len();
+        Ok(line)
+    }))
+}
+
+/// Splits `buf` into lines, each with the parts of the `matches` in `buf` that touch it. A match
+/// across lines is cut into one part for each.
+fn split_matches(buf: &[u8], matches: &[Range<usize>], terminator: u8) -> Vec<Line> {
+    let mut lines = Vec::new();
+    let mut offset = 0;
+    // The first match that may touch the line, as they are in order.
+    let mut first = 0;
+    for (i, text) in buf.split_inclusive(|&b| b == terminator).enumerate() {
+        let mut line = Line::new(i + 1, offset, text.to_vec(), terminator);
+        let end = offset + line.text.len();
+        while matches
+            .get(first)
+            .is_some_and(|m| m.end < offset || (m.end == offset && !m.is_empty()))
+        {
+            first += 1;
+        }
+        line.matches = matches[first..]
+            .iter()
+            .take_while(|m| m.start < end)
+            .map(|m| {
+                let start = m.start.clamp(offset, offset + line.len);
+                let end = m.end.clamp(start, offset + line.len);
+                start - offset..end - offset
+            })
+            .collect();
+        offset = end;
+        lines.push(line);
+    }
+    lines
+}
+
+/// Whether a line is selected, or printed as the context of a selected line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Selected,
+    Context,
+}
+
+impl Kind {
+    /// The separator after the file name, line number and byte offset.
+    fn separator(self) -> &'static str {
+        match self {
+            Kind::Selected => ":",
+            Kind::Context => "-",
+        }
+    }
+
+    fn match_color(self, colors: &Colors) -> &str {
+        match self {
+            Kind::Selected => &colors.selected_match,
+            Kind::Context => &colors.context_match,
+        }
+    }
+
+    fn line_color(self, colors: &Colors) -> &str {
+        match self {
+            Kind::Selected => &colors.selected_line,
+            Kind::Context => &colors.context_line,
+        }
+    }
+}
+
+/// How selected lines are printed.
+struct Printer {
+    line_number: bool,
+    byte_offset: bool,
+    only_matching: bool,
+    /// Written after each part with `only_matching`.
+    terminator: u8,
+    after_context: usize,
+    before_context: usize,
+    /// Printed between groups of lines, only when context is asked for.
+    group_separator: Option<String>,
+    colors: Option<Colors>,
+    /// Whether to replace invalid UTF-8 in lines.
+    lossy: bool,
+    /// Whether to print JSON events instead.
+    json: bool,
+    /// The names of the capture groups of the patterns, for JSON output.
+    group_names: Vec<Option<String>>,
+}
+
+impl Printer {
+    fn group_separator(&self) -> Option<&str> {
+        self.group_separator.as_deref()
+    }
+
+    fn paint<'a>(&self, sgr: impl Fn(&Colors) -> &str, text: &'a str) -> Cow<'a, str> {
+        match &self.colors {
+            Some(colors) => colors.paint(sgr(colors), text),
+            None => Cow::Borrowed(text),
+        }
+    }
+
+    /// Writes a part of a line, painted like `paint`.
+    fn write_part(
+        &self,
+        out: &mut impl Write,
+        sgr: impl Fn(&Colors) -> &str,
+        text: &[u8],
+    ) -> io::Result<()> {
+        let lossy;
+        let text = if self.lossy {
+            lossy = text.to_str_lossy();
+            lossy.as_bytes()
+        } else {
+            text
+        };
+        match &self.colors {
+            Some(colors) => colors.write(out, sgr(colors), text),
+            None => out.write_all(text),
+        }
+    }
+
+    fn write_filename(&self, out: &mut impl Write, path: &Path) -> io::Result<()> {
+        let filename = path.to_string_lossy();
+        write!(out, "{}", self.paint(|c| &c.filename, &filename))
+    }
+
+    /// Writes the file name, line number and byte offset that are asked for, each followed by a
+    /// separator.
+    fn write_prefix(
+        &self,
+        out: &mut impl Write,
+        path: Option<&Path>,
+        number: usize,
+        offset: usize,
+        kind: Kind,
+    ) -> io::Result<()> {
+        let separator = self.paint(|c| &c.separator, kind.separator());
+        if let Some(path) = path {
+            self.write_filename(out, path)?;
+            write!(out, "{}", separator)?;
+        }
+        if self.line_number {
+            let number = number.to_string();
+            write!(
+                out,
+                "{}{}",
+                self.paint(|c| &c.line_number, &number),
+                separator
+            )?;
+        }
+        if self.byte_offset {
+            let offset = offset.to_string();
+            write!(
+                out,
+                "{}{}",
+                self.paint(|c| &c.byte_offset, &offset),
+                separator
+            )?;
         }
-        // A new-line code is expected to be included in `line`.
-        print!("{}", line);
         Ok(())
-    })
+    }
+
+    fn write_line(
+        &self,
+        out: &mut impl Write,
+        path: Option<&Path>,
+        line: &Line,
+        kind: Kind,
+    ) -> io::Result<()> {
+        if self.json {
+            return json::write_line(out, path, line, kind, &self.group_names);
+        }
+        if self.only_matching {
+            // Like GNU grep, context lines are left out, but not the group separators.
+            if kind == Kind::Context {
+                return Ok(());
+            }
+            for (i, m) in line.parts().enumerate() {
+                self.write_prefix(out, path, line.number, line.offset + m.start, kind)?;
+                self.write_part(out, |c| kind.match_color(c), line.part(i, m))?;
+                out.write_all(&[self.terminator])?;
+            }
+            return Ok(());
+        }
+        self.write_prefix(out, path, line.number, line.offset, kind)?;
+        let mut last = 0;
+        for (i, m) in line.parts().enumerate() {
+            self.write_part(out, |c| kind.line_color(c), &line.text[last..m.start])?;
+            self.write_part(out, |c| kind.match_color(c), line.part(i, m))?;
+            last = m.end;
+        }
+        self.write_part(out, |c| kind.line_color(c), &line.text[last..line.len])?;
+        // The line terminator is printed as it is, if any.
+        out.write_all(&line.text[line.len..])
+    }
+}
+
+/// What is printed for each file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Lines,
+    Count,
+    FilesWithMatches,
+    FilesWithoutMatch,
+    Quiet,
+    /// Replacing the matches in the files.
+    Edit,
 }
 
-fn print_count<I>(mut iter: I, path: &Path, should_print_path: bool) -> io::Result<()>
-where
-    I: Iterator<Item = io::Result<String>>,
-{
-    let count = iter.try_fold(0, |acc, _| Ok::<usize, io::Error>(acc + 1))?;
-    if should_print_path {
-        print!("{}:", path.to_string_lossy());
+/// What was found in the files searched.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+struct Stats {
+    files_searched: usize,
+    files_matched: usize,
+    matched_lines: usize,
+    matches: usize,
+}
+
+impl Stats {
+    /// Whether a line was selected.
+    fn selected(&self) -> bool {
+        self.matched_lines > 0
     }
-    println!("{}", count);
-    Ok(())
 }
 
-fn grep(re: &Regex, path: &Path, should_print_path: bool, count: bool) -> Result<(), IOError> {
-    let iter = open(path)?.lines_nl().filter_ok(|line| re.is_match(line));
-    if count {
-        print_count(iter, path, should_print_path)
-    } else {
-        print_line(iter, path, should_print_path)
+impl AddAssign for Stats {
+    fn add_assign(&mut self, other: Stats) {
+        self.files_searched += other.files_searched;
+        self.files_matched += other.files_matched;
+        self.matched_lines += other.matched_lines;
+        self.matches += other.matches;
     }
-    .map_err(|e| IOError(path.to_owned(), e))?;
-    Ok(())
 }
 
-fn grep_recursive(re: &Regex, path: &Path, count: bool) -> Result<(), IOError> {
-    WalkDir::new(path)
-        .into_iter()
-        .filter_ok(|entry| entry.file_type().is_file())
-        .try_for_each(|entry| {
-            grep(re, entry?.path(), true, count)?;
-            Ok(())
+/// What to search for and how to print it.
+struct Grep {
+    matcher: Matcher,
+    replace: Option<String>,
+    backup_suffix: Option<String>,
+    invert_match: bool,
+    mode: Mode,
+    max_count: Option<usize>,
+    binary_files: BinaryFiles,
+    filter: Filter,
+    jobs: usize,
+    sort_files: bool,
+    terminator: u8,
+    multiline: bool,
+    no_messages: bool,
+    printer: Printer,
+}
+
+/// The output of a file searched by a worker, and what was found.
+struct Searched {
+    path: PathBuf,
+    output: Output<Vec<u8>>,
+    result: Result<Stats, IOError>,
+}
+
+impl Grep {
+    /// Prints an error about a file, unless `-s` is given.
+    fn report(&self, e: &IOError) {
+        if !self.no_messages {
+            eprintln!("{}", e);
+        }
+    }
+
+    fn is_selected(&self, line: &Line) -> bool {
+        line.matches.is_empty() == self.invert_match
+    }
+
+    /// Searches the file `path`, and returns what was found.
+    fn grep<W: Write>(
+        &self,
+        out: &mut Output<W>,
+        path: &Path,
+        with_filename: bool,
+    ) -> Result<Stats, IOError> {
+        let io_error = |e| IOError(path.to_owned(), e);
+        let json = self.printer.json;
+        // JSON events always have the path.
+        let path_prefix = (with_filename || json).then_some(path);
+        let mut reader = open(path)?;
+        // NUL bytes are the terminators of null data.
+        let binary = self.binary_files != BinaryFiles::Text
+            && self.terminator != b'\0'
+            && reader.fill_buf().map_err(io_error)?.contains(&0);
+        if binary && self.binary_files == BinaryFiles::WithoutMatch {
+            reader = Box::new(io::empty());
+        }
+        let lines = lines(reader, &self.matcher, self.terminator, self.multiline);
+        // Binary files are not edited.
+        if self.mode == Mode::Edit {
+            return if binary {
+                Ok(Stats {
+                    files_searched: 1,
+                    ..Stats::default()
+                })
+            } else {
+                self.edit(out, path, lines)
+            };
+        }
+        if json {
+            json::write_begin(&mut out.writer, path).map_err(io_error)?;
+        }
+        let (count, matches) = if self.mode == Mode::Lines && !binary {
+            self.grep_lines(out, path_prefix, lines).map_err(io_error)?
+        } else {
+            self.grep_count(out, path, path_prefix, binary, lines)
+                .map_err(io_error)?
+        };
+        let stats = Stats {
+            files_searched: 1,
+            files_matched: usize::from(count > 0),
+            matched_lines: count,
+            matches,
+        };
+        if json {
+            json::write_end(&mut out.writer, path, binary, &stats).map_err(io_error)?;
+        }
+        Ok(stats)
+    }
+
+    /// Prints the selected lines with their context, and returns the numbers of selected lines
+    /// and of matches in them.
+    fn grep_lines<W: Write>(
+        &self,
+        out: &mut Output<W>,
+        path_prefix: Option<&Path>,
+        lines: impl Iterator<Item = io::Result<Line>>,
+    ) -> io::Result<(usize, usize)> {
+        let max_count = self.max_count.unwrap_or(usize::MAX);
+        let mut writer = ContextWriter::new(&self.printer, out, path_prefix);
+        let mut count = 0;
+        let mut matches = 0;
+        for line in lines {
+            let mut line = line?;
+            if count == max_count {
+                // Like GNU grep, the trailing context is still printed, even selected lines.
+                if !writer.has_after_context() {
+                    break;
+                }
+                writer.write(line, false)?;
+            } else {
+                let selected = self.is_selected(&line);
+                if selected {
+                    count += 1;
+                    matches += line.parts().count();
+                    if self.printer.json {
+                        line.captures = self.matcher.captures(line.content(), line.parts());
+                    }
+                    if let Some(template) = &self.replace {
+                        line.replacements =
+                            self.matcher.expand(line.content(), line.parts(), template);
+                    }
+                }
+                writer.write(line, selected)?;
+            }
+        }
+        Ok((count, matches))
+    }
+
+    /// Counts the selected lines and the matches in them, and prints the count, the file name, or
+    /// that a binary file matches, as the mode says.
+    fn grep_count<W: Write>(
+        &self,
+        out: &mut Output<W>,
+        path: &Path,
+        path_prefix: Option<&Path>,
+        binary: bool,
+        lines: impl Iterator<Item = io::Result<Line>>,
+    ) -> io::Result<(usize, usize)> {
+        // Only counting goes on after the first selected line.
+        let max_count = self.max_count.unwrap_or(usize::MAX);
+        let max_count = match self.mode {
+            Mode::Count => max_count,
+            _ => max_count.min(1),
+        };
+        let (count, matches) = lines
+            .filter_ok(|line| self.is_selected(line))
+            .take(max_count)
+            .fold_ok((0, 0), |(count, matches), line| {
+                (count + 1, matches + line.parts().count())
+            })?;
+        let out = &mut out.writer;
+        match self.mode {
+            // JSON events have the counts already.
+            _ if self.printer.json => {}
+            Mode::Count => {
+                if let Some(path) = path_prefix {
+                    self.printer.write_filename(out, path)?;
+                    let separator = self.printer.paint(|c| &c.separator, ":");
+                    write!(out, "{}", separator)?;
+                }
+                writeln!(out, "{}", count)?;
+            }
+            Mode::Lines if binary && count > 0 => {
+                writeln!(out, "Binary file {} matches", path.display())?;
+            }
+            Mode::FilesWithMatches | Mode::FilesWithoutMatch
+                if (count > 0) == (self.mode == Mode::FilesWithMatches) =>
+            {
+                self.printer.write_filename(out, path)?;
+                writeln!(out)?;
+            }
+            _ => {}
+        }
+        Ok((count, matches))
+    }
+
+    /// Replaces the matches in the selected lines of the file `path`, and prints the number of
+    /// substitutions. The file is only written if it changes, through a temporary file in the
+    /// same directory that is renamed over it, after copying it to a backup if asked for.
+    fn edit<W: Write>(
+        &self,
+        out: &mut Output<W>,
+        path: &Path,
+        lines: impl Iterator<Item = io::Result<Line>>,
+    ) -> Result<Stats, IOError> {
+        let io_error = |e| IOError(path.to_owned(), e);
+        let template = self
+            .replace
+            .as_deref()
+            .expect("--in-place requires --replace");
+        let Some(name) = path.file_name().filter(|_| path != Path::new("-")) else {
+            let e = io::Error::new(io::ErrorKind::InvalidInput, "cannot be edited in place");
+            return Err(io_error(e));
+        };
+        let max_count = self.max_count.unwrap_or(usize::MAX);
+        let mut text = Vec::new();
+        let mut count = 0;
+        let mut matches = 0;
+        for line in lines {
+            let mut line = line.map_err(io_error)?;
+            if count < max_count && self.is_selected(&line) && line.parts().next().is_some() {
+                line.replacements = self.matcher.expand(line.content(), line.parts(), template);
+                count += 1;
+                matches += line.replacements.len();
+                text.extend(line.replaced());
+            } else {
+                text.extend(line.text);
+            }
+        }
+        if matches > 0 {
+            let mut temp_name = OsString::from(".");
+            temp_name.push(name);
+            temp_name.push(format!(".{}.tmp", std::process::id()));
+            let temp = path.with_file_name(temp_name);
+            let write = || {
+                let permissions = fs::metadata(path)?.permissions();
+                fs::write(&temp, &text)?;
+                fs::set_permissions(&temp, permissions)?;
+                if let Some(suffix) = &self.backup_suffix {
+                    let mut backup = path.as_os_str().to_owned();
+                    backup.push(suffix);
+                    fs::copy(path, backup)?;
+                }
+                fs::rename(&temp, path)
+            };
+            if let Err(e) = write() {
+                let _ = fs::remove_file(&temp);
+                return Err(io_error(e));
+            }
+            let out = &mut out.writer;
+            self.printer.write_filename(out, path).map_err(io_error)?;
+            let separator = self.printer.paint(|c| &c.separator, ":");
+            let plural = |n: usize| if n == 1 { "" } else { "s" };
+            writeln!(
+                out,
+                "{} {} substitution{} in {} line{}",
+                separator,
+                matches,
+                plural(matches),
+                count,
+                plural(count)
+            )
+            .map_err(io_error)?;
+        }
+        Ok(Stats {
+            files_searched: 1,
+            files_matched: usize::from(count > 0),
+            matched_lines: count,
+            matches,
         })
+    }
+
+    /// Searches the files under `path`, and returns what was found. The files are
+    /// searched by a pool of workers, each into its own buffer, and the buffers are printed as
+    /// soon as they are filled, or in the order of the walk with `--sort-files`. Like grep, an
+    /// error in a file is reported in its place and sets `has_error`, and the search goes on;
+    /// only an error writing the output stops it.
+    fn grep_recursive<W: Write>(
+        &self,
+        out: &mut Output<W>,
+        path: &Path,
+        with_filename: bool,
+        has_error: &mut bool,
+    ) -> Result<Stats, IOError> {
+        let stop = AtomicBool::new(false);
+        let (job_sender, job_receiver) = mpsc::sync_channel(self.jobs);
+        let job_receiver = Mutex::new(job_receiver);
+        let (sender, receiver) = mpsc::channel();
+        thread::scope(|scope| {
+            let stop = &stop;
+            scope.spawn(move || {
+                let files = self.filter.walk(path, self.sort_files).filter(|entry| {
+                    entry.as_ref().map_or(true, |entry| {
+                        entry.file_type().is_file() && self.filter.is_included(entry.path())
+                    })
+                });
+                for job in files.enumerate() {
+                    if stop.load(Ordering::Relaxed) || job_sender.send(job).is_err() {
+                        break;
+                    }
+                }
+            });
+            for _ in 0..self.jobs {
+                let job_receiver = &job_receiver;
+                let sender = sender.clone();
+                scope.spawn(move || {
+                    loop {
+                        // The lock is released before searching.
+                        let job = job_receiver.lock().unwrap().recv();
+                        let Ok((index, entry)) = job else {
+                            break;
+                        };
+                        // The jobs left are drained, so that the walk is not blocked.
+                        if stop.load(Ordering::Relaxed) {
+                            continue;
+                        }
+                        let searched = self.search(entry, with_filename);
+                        if sender.send((index, searched)).is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+            drop(sender);
+
+            let mut stats = Stats::default();
+            let mut pending = BTreeMap::new();
+            let mut next = 0;
+            for (index, searched) in receiver {
+                pending.insert(index, searched);
+                while let Some(searched) = if self.sort_files {
+                    pending.remove(&next)
+                } else {
+                    pending.pop_first().map(|(_, searched)| searched)
+                } {
+                    next += 1;
+                    if let Err(e) = out.append(&self.printer, searched.output) {
+                        stop.store(true, Ordering::Relaxed);
+                        return Err(IOError(searched.path, e));
+                    }
+                    match searched.result {
+                        Ok(found) => stats += found,
+                        Err(e) => {
+                            self.report(&e);
+                            *has_error = true;
+                        }
+                    }
+                    if stats.selected() && self.mode == Mode::Quiet {
+                        stop.store(true, Ordering::Relaxed);
+                        return Ok(stats);
+                    }
+                }
+            }
+            Ok(stats)
+        })
+    }
+
+    /// Searches an entry of a walk into a buffer.
+    fn search(&self, entry: walkdir::Result<walkdir::DirEntry>, with_filename: bool) -> Searched {
+        let mut output = Output::new(Vec::new());
+        match entry {
+            Ok(entry) => {
+                let result = self.grep(&mut output, entry.path(), with_filename);
+                Searched {
+                    path: entry.into_path(),
+                    output,
+                    result,
+                }
+            }
+            Err(e) => Searched {
+                path: e.path().map(ToOwned::to_owned).unwrap_or_default(),
+                output,
+                result: Err(e.into()),
+            },
+        }
+    }
 }
 
-pub fn run(args: Args) -> Result<(), MyError> {
-    let should_print_path = args.files.len() > 1;
-    let re = args.build_regex().map_err(|e| {
+/// Returns whether a line was selected. Like grep, an error in a file does not stop the search,
+/// but it is an error in the end unless a line was selected with `-q`.
+pub fn run(args: Args) -> Result<bool, MyError> {
+    let with_filename = args.with_filename(args.recursive || args.files.len() > 1);
+    let matcher = args.build_matcher().map_err(|e| {
+        eprintln!("{}", e);
+        MyError()
+    })?;
+    let filter = args.build_filter().map_err(|e| {
         eprintln!("{}", e);
         MyError()
     })?;
+    let grep = Grep {
+        replace: args.replace.clone(),
+        backup_suffix: args.backup_suffix.clone(),
+        invert_match: args.invert_match,
+        mode: args.mode(),
+        max_count: args.max_count,
+        binary_files: args.binary_files(),
+        filter,
+        jobs: args.jobs.get(),
+        sort_files: args.sort_files,
+        terminator: args.terminator(),
+        multiline: args.multiline,
+        no_messages: args.no_messages,
+        printer: args.printer(&matcher),
+        matcher,
+    };
 
+    let mut out = Output::new(io::stdout().lock());
+    let mut stats = Stats::default();
     let mut has_error = false;
     for path in &args.files {
+        if !args.recursive && !grep.filter.is_included(path) {
+            continue;
+        }
         let result = if args.recursive {
-            grep_recursive(&re, path, args.count)
+            grep.grep_recursive(&mut out, path, with_filename, &mut has_error)
         } else {
-            grep(&re, path, should_print_path, args.count)
+            grep.grep(&mut out, path, with_filename)
         };
-        if let Err(e) = result {
-            eprintln!("{}", e);
-            has_error = true;
+        match result {
+            Ok(found) => stats += found,
+            Err(e) => {
+                grep.report(&e);
+                has_error = true;
+            }
         }
+        if stats.selected() && args.quiet {
+            break;
+        }
+    }
+    if grep.printer.json
+        && let Err(e) = json::write_summary(&mut out.writer, &stats)
+    {
+        eprintln!("{}", e);
+        has_error = true;
+    }
+    if has_error && !(stats.selected() && args.quiet) {
+        Err(MyError())
+    } else {
+        Ok(stats.selected())
     }
-    if has_error { Err(MyError()) } else { Ok(()) }
 }
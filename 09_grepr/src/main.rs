@@ -3,7 +3,11 @@ use grepr::{self, Args};
 
 fn main() {
     let args = Args::parse().set_default_paths_if_empty();
-    if grepr::run(args).is_err() {
-        std::process::exit(1);
-    }
+    // Like grep: 0 if a line is selected, 1 if none is, and 2 on error.
+    let code = match grepr::run(args) {
+        Ok(true) => 0,
+        Ok(false) => 1,
+        Err(_) => 2,
+    };
+    std::process::exit(code);
 }
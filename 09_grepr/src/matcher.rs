@@ -0,0 +1,371 @@
+use aho_corasick::{AhoCorasick, AhoCorasickBuilder, Anchored, Input, MatchKind, StartKind};
+use bstr::ByteSlice;
+use regex::{
+    self,
+    bytes::{Captures, Regex, RegexBuilder},
+};
+use std::cmp::Reverse;
+use std::ops::Range;
+
+/// How patterns are matched.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Options {
+    pub fixed_strings: bool,
+    pub ignore_case: bool,
+    /// Only match whole words, like GNU grep's `-w`.
+    pub word: bool,
+    /// Only match whole lines.
+    pub line: bool,
+    /// Match against a whole buffer, where `^` and `$` match at the start and end of lines.
+    pub multiline: bool,
+    /// Whether the capture groups are needed, which only regexes have.
+    pub captures: bool,
+}
+
+/// Finds the matches of a set of patterns in a line of bytes, which may not be valid UTF-8:
+/// regular expressions are combined into one regex, and fixed strings are searched all at once
+/// with an Aho-Corasick automaton.
+#[derive(Debug)]
+pub enum Matcher {
+    Regex(Regex),
+    Literals {
+        automaton: AhoCorasick,
+        word: bool,
+        line: bool,
+    },
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Whether `text[range]` is neither preceded nor followed by a word character.
+fn is_word(text: &[u8], range: &Range<usize>) -> bool {
+    let before = text[..range.start].chars().next_back();
+    let after = text[range.end..].chars().next();
+    !before.is_some_and(is_word_char) && !after.is_some_and(is_word_char)
+}
+
+impl Matcher {
+    pub fn new(patterns: &[String], options: Options) -> Result<Matcher, regex::Error> {
+        // The automaton only ignores the case of ASCII letters.
+        let ascii = patterns.iter().all(|pattern| pattern.is_ascii());
+        if options.fixed_strings
+            && (ascii || !options.ignore_case)
+            && !options.multiline
+            && !options.captures
+        {
+            let (kind, start) = if options.line {
+                (MatchKind::LeftmostLongest, StartKind::Anchored)
+            } else if options.word {
+                // Every candidate is needed, as a longer one may not be a whole word.
+                (MatchKind::Standard, StartKind::Unanchored)
+            } else {
+                (MatchKind::LeftmostLongest, StartKind::Unanchored)
+            };
+            let automaton = AhoCorasickBuilder::new()
+                .match_kind(kind)
+                .start_kind(start)
+                .ascii_case_insensitive(options.ignore_case)
+                .build(patterns)
+                .expect("the automaton should fit in memory");
+            return Ok(Matcher::Literals {
+                automaton,
+                word: options.word,
+                line: options.line,
+            });
+        }
+
+        let alternatives = patterns
+            .iter()
+            .map(|pattern| {
+                if options.fixed_strings {
+                    format!("(?:{})", regex::escape(pattern))
+                } else {
+                    format!("(?:{pattern})")
+                }
+            })
+            .collect::<Vec<_>>();
+        let pattern = if alternatives.is_empty() {
+            // Without patterns, nothing matches.
+            r"[^\s\S]".to_string()
+        } else {
+            alternatives.join("|")
+        };
+        let pattern = if options.line {
+            format!("^(?:{pattern})$")
+        } else if options.word {
+            format!(r"\b{{start-half}}(?:{pattern})\b{{end-half}}")
+        } else {
+            pattern
+        };
+        let re = RegexBuilder::new(&pattern)
+            .case_insensitive(options.ignore_case)
+            .multi_line(options.multiline)
+            .build()?;
+        Ok(Matcher::Regex(re))
+    }
+
+    /// The names of the capture groups of the patterns, starting with group 1.
+    pub fn group_names(&self) -> Vec<Option<String>> {
+        match self {
+            Matcher::Regex(re) => re
+                .capture_names()
+                .skip(1)
+                .map(|name| name.map(ToOwned::to_owned))
+                .collect(),
+            Matcher::Literals { .. } => Vec::new(),
+        }
+    }
+
+    /// The capture groups of the match `m` found in `text`, if the patterns are regexes and the
+    /// match is found again there, which a part of a match across lines is not.
+    fn captures_of<'t>(&self, text: &'t [u8], m: &Range<usize>) -> Option<Captures<'t>> {
+        let Matcher::Regex(re) = self else {
+            return None;
+        };
+        re.captures_at(text, m.start)
+            .filter(|captures| captures.get(0).is_some_and(|all| all.range() == *m))
+    }
+
+    /// Returns the positions of the capture groups of each of the `matches` found in `text`,
+    /// starting with group 1. Fixed strings have none.
+    pub fn captures<'a>(
+        &self,
+        text: &[u8],
+        matches: impl Iterator<Item = &'a Range<usize>>,
+    ) -> Vec<Vec<Option<Range<usize>>>> {
+        matches
+            .map(|m| {
+                self.captures_of(text, m)
+                    .map(|captures| {
+                        captures
+                            .iter()
+                            .skip(1)
+                            .map(|group| group.map(|group| group.range()))
+                            .collect()
+                    })
+                    .unwrap_or_default()
+            })
+            .collect()
+    }
+
+    /// Returns the replacement of each of the `matches` found in `text` by `template`, where
+    /// `$1` or `${name}` stand for a capture group. A match that is not found again is kept.
+    pub fn expand<'a>(
+        &self,
+        text: &[u8],
+        matches: impl Iterator<Item = &'a Range<usize>>,
+        template: &str,
+    ) -> Vec<Vec<u8>> {
+        matches
+            .map(|m| match self.captures_of(text, m) {
+                Some(captures) => {
+                    let mut replacement = Vec::new();
+                    captures.expand(template.as_bytes(), &mut replacement);
+                    replacement
+                }
+                None => text[m.clone()].to_vec(),
+            })
+            .collect()
+    }
+
+    /// Returns the positions of the matches in `text`, in order and without overlap.
+    pub fn find_iter(&self, text: &[u8]) -> Vec<Range<usize>> {
+        match self {
+            Matcher::Regex(re) => re.find_iter(text).map(|m| m.range()).collect(),
+            Matcher::Literals {
+                automaton,
+                line: true,
+                ..
+            } => {
+                let input = Input::new(text).anchored(Anchored::Yes);
+                match automaton.find(input) {
+                    Some(m) if m.end() == text.len() => vec![m.range()],
+                    _ => vec![],
+                }
+            }
+            Matcher::Literals {
+                automaton,
+                word: true,
+                ..
+            } => {
+                let mut candidates = automaton
+                    .find_overlapping_iter(text)
+                    .map(|m| m.range())
+                    .filter(|range| is_word(text, range))
+                    .collect::<Vec<_>>();
+                // The leftmost-longest ones, like a regex.
+                candidates.sort_by_key(|range| (range.start, Reverse(range.end)));
+                let mut matches: Vec<Range<usize>> = Vec::new();
+                for range in candidates {
+                    if matches.last().is_none_or(|last| last.end <= range.start) {
+                        matches.push(range);
+                    }
+                }
+                matches
+            }
+            Matcher::Literals { automaton, .. } => {
+                automaton.find_iter(text).map(|m| m.range()).collect()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn patterns(patterns: &[&str]) -> Vec<String> {
+        patterns.iter().map(|pattern| pattern.to_string()).collect()
+    }
+
+    fn find(patterns: &[String], options: Options, text: impl AsRef<[u8]>) -> Vec<(usize, usize)> {
+        let matcher = Matcher::new(patterns, options).unwrap();
+        matcher
+            .find_iter(text.as_ref())
+            .iter()
+            .map(|m| (m.start, m.end))
+            .collect()
+    }
+
+    #[test]
+    fn test_regexes() {
+        let options = Options::default();
+        let patterns = patterns(&["fo+", "b.r"]);
+        assert_eq!(find(&patterns, options, "foo bar"), [(0, 3), (4, 7)]);
+    }
+
+    #[test]
+    fn test_no_pattern() {
+        let options = Options::default();
+        assert!(find(&[], options, "foo").is_empty());
+    }
+
+    #[test]
+    fn test_word() {
+        let options = Options {
+            word: true,
+            ..Options::default()
+        };
+        let patterns = patterns(&["foo", "foo_bar"]);
+        assert_eq!(
+            find(&patterns, options, "foo foobar foo_bar"),
+            [(0, 3), (11, 18)]
+        );
+        let options = Options {
+            fixed_strings: true,
+            ..options
+        };
+        assert_eq!(
+            find(&patterns, options, "foo foobar foo_bar"),
+            [(0, 3), (11, 18)]
+        );
+    }
+
+    #[test]
+    fn test_line() {
+        let options = Options {
+            line: true,
+            ..Options::default()
+        };
+        let patterns = patterns(&["foo", "foo bar"]);
+        assert_eq!(find(&patterns, options, "foo bar"), [(0, 7)]);
+        assert!(find(&patterns, options, "foo bar baz").is_empty());
+        let options = Options {
+            fixed_strings: true,
+            ..options
+        };
+        assert_eq!(find(&patterns, options, "foo bar"), [(0, 7)]);
+        assert!(find(&patterns, options, "foo bar baz").is_empty());
+        let options = Options {
+            word: true,
+            ..options
+        };
+        assert_eq!(find(&patterns, options, "foo bar"), [(0, 7)]);
+    }
+
+    #[test]
+    fn test_fixed_strings() {
+        let options = Options {
+            fixed_strings: true,
+            ignore_case: true,
+            ..Options::default()
+        };
+        let patterns = patterns(&["a.b", "A.B.C"]);
+        assert_eq!(find(&patterns, options, "axb a.b.c"), [(4, 9)]);
+        // Non-ASCII letters fall back to a regex.
+        let patterns = self::patterns(&["É"]);
+        assert_eq!(find(&patterns, options, "été"), [(0, 2), (3, 5)]);
+    }
+
+    #[test]
+    fn test_captures() {
+        let patterns = patterns(&["(?<key>[a-z]+)=([0-9]+)?"]);
+        let matcher = Matcher::new(&patterns, Options::default()).unwrap();
+        assert_eq!(matcher.group_names(), [Some("key".to_string()), None]);
+        let text = b"a=1 b=";
+        let matches = matcher.find_iter(text);
+        assert_eq!(
+            matcher.captures(text, matches.iter()),
+            [vec![Some(0..1), Some(2..3)], vec![Some(4..5), None]]
+        );
+    }
+
+    #[test]
+    fn test_expand() {
+        let options = Options {
+            fixed_strings: true,
+            captures: true,
+            ..Options::default()
+        };
+        let matcher = Matcher::new(&patterns(&["a.b"]), options).unwrap();
+        let text = b"a.b axb";
+        let matches = matcher.find_iter(text);
+        assert_eq!(matcher.expand(text, matches.iter(), "<$0>"), [b"<a.b>"]);
+
+        let patterns = patterns(&[r"(?<key>\w+)=(\w+)"]);
+        let matcher = Matcher::new(&patterns, Options::default()).unwrap();
+        let text = b"a=1 b=2";
+        let matches = matcher.find_iter(text);
+        assert_eq!(
+            matcher.expand(text, matches.iter(), "$2=${key}"),
+            [b"1=a", b"2=b"]
+        );
+    }
+
+    #[test]
+    fn test_multiline() {
+        let options = Options {
+            multiline: true,
+            ..Options::default()
+        };
+        let patterns = patterns(&["o$", r"r\nb", "^c"]);
+        assert_eq!(
+            find(&patterns, options, "foo\nbar\nbaz\ncat"),
+            [(2, 3), (6, 9), (12, 13)]
+        );
+        let options = Options {
+            fixed_strings: true,
+            line: true,
+            ..options
+        };
+        let patterns = self::patterns(&["bar"]);
+        assert_eq!(find(&patterns, options, "foo\nbar\nbaz"), [(4, 7)]);
+    }
+
+    #[test]
+    fn test_invalid_utf8() {
+        let options = Options::default();
+        let patterns = patterns(&["caf."]);
+        assert!(find(&patterns, options, b"caf\xe9").is_empty());
+        assert_eq!(find(&patterns, options, b"\xffcaf\xc3\xa9"), [(1, 6)]);
+        let options = Options {
+            fixed_strings: true,
+            word: true,
+            ..options
+        };
+        let patterns = self::patterns(&["foo"]);
+        assert_eq!(find(&patterns, options, b"\xfffoo\xff"), [(1, 4)]);
+    }
+}
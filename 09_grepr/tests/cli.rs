@@ -3,7 +3,7 @@ use assert_cmd::Command;
 use predicates::prelude::*;
 use pretty_assertions::assert_eq;
 use rand::{Rng, distributions::Alphanumeric};
-use std::{fs, path::Path};
+use std::{fs, os::unix::fs::PermissionsExt, path::Path};
 use sys_info::os_type;
 
 const PRG: &str = "grepr";
@@ -11,6 +11,13 @@ const BUSTLE: &str = "tests/inputs/bustle.txt";
 const EMPTY: &str = "tests/inputs/empty.txt";
 const FOX: &str = "tests/inputs/fox.txt";
 const NOBODY: &str = "tests/inputs/nobody.txt";
+const LOG: &str = "tests/inputs/log.txt";
+const PATTERNS: &str = "tests/inputs/patterns.txt";
+const BINARY: &str = "tests/inputs/binary.dat";
+const TREE: &str = "tests/inputs/tree";
+const LATIN1: &str = "tests/inputs/latin1.txt";
+const RECORDS: &str = "tests/inputs/records.bin";
+const SIGNATURES: &str = "tests/inputs/signatures.rs";
 const INPUTS_DIR: &str = "tests/inputs";
 
 // --------------------------------------------------
@@ -64,6 +71,16 @@ fn warns_bad_file() -> Result<()> {
 
 // --------------------------------------------------
 fn run(args: &[&str], expected_file: &str) -> Result<()> {
+    run_with_code(args, expected_file, 0)
+}
+
+// --------------------------------------------------
+fn run_no_match(args: &[&str], expected_file: &str) -> Result<()> {
+    run_with_code(args, expected_file, 1)
+}
+
+// --------------------------------------------------
+fn run_with_code(args: &[&str], expected_file: &str, code: i32) -> Result<()> {
     let windows_file = format!("{expected_file}.windows");
     let expected_file = if os_type().unwrap() == "Windows" && Path::new(&windows_file).is_file() {
         &windows_file
@@ -73,17 +90,26 @@ fn run(args: &[&str], expected_file: &str) -> Result<()> {
 
     let expected = fs::read_to_string(expected_file)?;
     let output = Command::cargo_bin(PRG)?.args(args).output().expect("fail");
-    assert!(output.status.success());
+    assert_eq!(output.status.code(), Some(code));
 
     let stdout = String::from_utf8(output.stdout).expect("invalid UTF-8");
     assert_eq!(stdout, expected);
     Ok(())
 }
 
+// --------------------------------------------------
+fn run_raw(args: &[&str], expected_file: &str) -> Result<()> {
+    let expected = fs::read(expected_file)?;
+    let output = Command::cargo_bin(PRG)?.args(args).output().expect("fail");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(output.stdout, expected);
+    Ok(())
+}
+
 // --------------------------------------------------
 #[test]
 fn empty_file() -> Result<()> {
-    run(&["foo", EMPTY], "tests/expected/empty.foo")
+    run_no_match(&["foo", EMPTY], "tests/expected/empty.foo")
 }
 
 // --------------------------------------------------
@@ -119,7 +145,7 @@ fn bustle_ignore_case() -> Result<()> {
 // --------------------------------------------------
 #[test]
 fn nobody() -> Result<()> {
-    run(&["nobody", NOBODY], "tests/expected/nobody.txt")
+    run_no_match(&["nobody", NOBODY], "tests/expected/nobody.txt")
 }
 
 // --------------------------------------------------
@@ -197,7 +223,7 @@ fn insensitive_count() -> Result<()> {
 // --------------------------------------------------
 #[test]
 fn nobody_count() -> Result<()> {
-    run(&["-c", "nobody", NOBODY], "tests/expected/nobody.txt.count")
+    run_no_match(&["-c", "nobody", NOBODY], "tests/expected/nobody.txt.count")
 }
 
 // --------------------------------------------------
@@ -285,3 +311,685 @@ fn stdin_insensitive_count() -> Result<()> {
     assert_eq!(stdout, expected);
     Ok(())
 }
+
+// --------------------------------------------------
+#[test]
+fn line_number_byte_offset() -> Result<()> {
+    run(
+        &["-nb", "the", FOX, BUSTLE],
+        "tests/expected/the.line_number.byte_offset",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn only_matching_byte_offset() -> Result<()> {
+    run(
+        &["-o", "-b", "-H", "o.", FOX],
+        "tests/expected/fox.txt.only_matching.byte_offset",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn no_filename() -> Result<()> {
+    run(
+        &["-h", "-n", "-i", "the", BUSTLE, NOBODY],
+        "tests/expected/the.no_filename.insensitive",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn with_filename_count() -> Result<()> {
+    run(
+        &["-H", "-c", "the", FOX],
+        "tests/expected/fox.txt.the.with_filename.count",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn color_always() -> Result<()> {
+    run(
+        &["--color=always", "-Hn", "the", FOX],
+        "tests/expected/fox.txt.the.color",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn color_never() -> Result<()> {
+    run(
+        &["--color=never", "The", BUSTLE],
+        "tests/expected/bustle.txt.the.capitalized",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn grep_colors() -> Result<()> {
+    let expected = fs::read_to_string("tests/expected/fox.txt.fox.grep_colors")?;
+    Command::cargo_bin(PRG)?
+        .args(["--colour=always", "-o", "-H", "fox", FOX])
+        .env("GREP_COLORS", "ms=04;32:fn=:ne")
+        .assert()
+        .success()
+        .stdout(expected);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn after_context() -> Result<()> {
+    run(&["-A1", "ERROR", LOG], "tests/expected/log.txt.error.after")
+}
+
+// --------------------------------------------------
+#[test]
+fn before_context() -> Result<()> {
+    run(
+        &["-B", "2", "ERROR", LOG],
+        "tests/expected/log.txt.error.before",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn context_line_number() -> Result<()> {
+    run(
+        &["-C1", "-n", "ERROR", LOG],
+        "tests/expected/log.txt.error.context.line_number",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn invert_after_context() -> Result<()> {
+    run(
+        &["-vn", "-A1", "INFO", LOG],
+        "tests/expected/log.txt.info.invert.after",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn context_count() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["-c", "-C2", "ERROR", LOG])
+        .assert()
+        .success()
+        .stdout("2\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn context_group_separator() -> Result<()> {
+    run(
+        &["-C1", "--group-separator", "==", "ERROR", LOG],
+        "tests/expected/log.txt.error.context.group_separator",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn context_no_group_separator() -> Result<()> {
+    run(
+        &["-C1", "--no-group-separator", "ERROR", LOG],
+        "tests/expected/log.txt.error.context.no_group_separator",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn context_multiple_files() -> Result<()> {
+    run(
+        &["-C1", "-nb", "disk|fox", LOG, FOX],
+        "tests/expected/disk.fox.context.multiple",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn multiple_patterns() -> Result<()> {
+    run(
+        &["-e", "ERROR", "-e", "WARN", LOG],
+        "tests/expected/log.txt.error.warn",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn patterns_file() -> Result<()> {
+    run(
+        &["-n", "-f", PATTERNS, LOG],
+        "tests/expected/log.txt.patterns.line_number",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn fixed_strings() -> Result<()> {
+    run(
+        &["-F", "-e", ".", "-e", "api/items", LOG],
+        "tests/expected/log.txt.fixed_strings",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn word_regexp() -> Result<()> {
+    run(
+        &["-w", "-o", "-e", "the", "-e", "he", BUSTLE],
+        "tests/expected/bustle.txt.the.he.word.only_matching",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn fixed_strings_word_regexp() -> Result<()> {
+    run(
+        &["-Fw", "-o", "-e", "the", "-e", "he", BUSTLE],
+        "tests/expected/bustle.txt.the.he.word.only_matching",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn line_regexp() -> Result<()> {
+    run(
+        &["-x", "-e", "INFO retrying", "-e", "WARN", LOG],
+        "tests/expected/log.txt.line_regexp",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn fixed_strings_line_regexp() -> Result<()> {
+    run(
+        &["-Fx", "-e", "INFO retrying", "-e", "WARN", LOG],
+        "tests/expected/log.txt.line_regexp",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn empty_patterns_file() -> Result<()> {
+    run_no_match(&["-f", EMPTY, FOX], "tests/expected/empty.foo")
+}
+
+// --------------------------------------------------
+#[test]
+fn warns_bad_patterns_file() -> Result<()> {
+    let bad = gen_bad_file();
+    let expected = format!("{bad}: .* [(]os error 2[)]");
+    Command::cargo_bin(PRG)?
+        .args(["-f", &bad, FOX])
+        .assert()
+        .failure()
+        .stderr(predicate::str::is_match(expected)?);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn exits_one_without_match() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["nobody", FOX])
+        .assert()
+        .code(1)
+        .stdout("");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn exits_two_on_error() -> Result<()> {
+    let bad = gen_bad_file();
+    Command::cargo_bin(PRG)?
+        .args(["fox", &bad, FOX])
+        .assert()
+        .code(2)
+        .stdout(predicate::str::contains("fox"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn exits_two_on_bad_pattern() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["*foo", FOX])
+        .assert()
+        .code(2);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn quiet() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["-q", "fox", FOX])
+        .assert()
+        .code(0)
+        .stdout("");
+    Command::cargo_bin(PRG)?
+        .args(["-q", "nobody", FOX])
+        .assert()
+        .code(1)
+        .stdout("");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn quiet_masks_error_after_match() -> Result<()> {
+    let bad = gen_bad_file();
+    Command::cargo_bin(PRG)?
+        .args(["-q", "fox", &bad, FOX])
+        .assert()
+        .code(0)
+        .stdout("");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn no_messages() -> Result<()> {
+    let bad = gen_bad_file();
+    Command::cargo_bin(PRG)?
+        .args(["-s", "fox", &bad])
+        .assert()
+        .code(2)
+        .stderr("");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn files_with_matches() -> Result<()> {
+    run(
+        &["-l", "ERROR", LOG, FOX, EMPTY],
+        "tests/expected/error.files_with_matches",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn files_without_match() -> Result<()> {
+    run(
+        &["-L", "ERROR", LOG, FOX, EMPTY],
+        "tests/expected/error.files_without_match",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn max_count_after_context() -> Result<()> {
+    run(
+        &["-n", "-m1", "-A2", "request", LOG],
+        "tests/expected/log.txt.request.max_count.after",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn max_count_count() -> Result<()> {
+    run(
+        &["-c", "-m", "2", "INFO", LOG],
+        "tests/expected/log.txt.info.max_count.count",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn max_count_zero() -> Result<()> {
+    run_no_match(&["-m0", "fox", FOX], "tests/expected/empty.foo")
+}
+
+// --------------------------------------------------
+#[test]
+fn binary_file() -> Result<()> {
+    run(&["foo", BINARY], "tests/expected/binary.dat.foo")
+}
+
+// --------------------------------------------------
+#[test]
+fn binary_file_count() -> Result<()> {
+    run(
+        &["-c", "foo", BINARY],
+        "tests/expected/binary.dat.foo.count",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn binary_files_text() -> Result<()> {
+    run(
+        &["--binary-files=text", "foo", BINARY],
+        "tests/expected/binary.dat.foo.text",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn binary_files_without_match() -> Result<()> {
+    run_no_match(
+        &["--binary-files=without-match", "foo", BINARY],
+        "tests/expected/empty.foo",
+    )?;
+    // Like GNU grep, -L exits with 1 as no line is selected.
+    run_no_match(
+        &["-L", "--binary-files=without-match", "foo", BINARY],
+        "tests/expected/binary.dat.foo.without_match",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn recursive_include() -> Result<()> {
+    run(
+        &["-r", "--include=*.dat", "foo", INPUTS_DIR],
+        "tests/expected/foo.recursive.include",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn exclude_file() -> Result<()> {
+    run_no_match(
+        &["--exclude=*.dat", "foo", BINARY],
+        "tests/expected/empty.foo",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn recursive_exclude() -> Result<()> {
+    run(
+        &[
+            "-rl",
+            "--exclude-dir=skipped",
+            "--exclude=*.log",
+            "foo",
+            TREE,
+        ],
+        "tests/expected/tree.foo.exclude",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn recursive_respect_gitignore() -> Result<()> {
+    run(
+        &["-r", "--respect-gitignore", "foo", TREE],
+        "tests/expected/tree.foo.gitignore",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_glob() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--include=[a", "foo", FOX])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("unclosed character class"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn text() -> Result<()> {
+    run(&["-a", "foo", BINARY], "tests/expected/binary.dat.foo.text")
+}
+
+// --------------------------------------------------
+#[test]
+fn invalid_utf8() -> Result<()> {
+    run_raw(&["ok", LATIN1], "tests/expected/latin1.txt.ok")
+}
+
+// --------------------------------------------------
+#[test]
+fn invalid_utf8_lossy() -> Result<()> {
+    run(
+        &["--encoding=lossy", "ok", LATIN1],
+        "tests/expected/latin1.txt.ok.lossy",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn recursive_jobs_sorted_count() -> Result<()> {
+    run(
+        &["-rc", "-j4", "--sort-files", "foo", INPUTS_DIR],
+        "tests/expected/foo.recursive.jobs.sorted.count",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn recursive_jobs_sorted_context() -> Result<()> {
+    run(
+        &["-r", "-j4", "--sort-files", "-C1", "foo", INPUTS_DIR],
+        "tests/expected/foo.recursive.jobs.sorted.context",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn recursive_jobs_exit_codes() -> Result<()> {
+    run(
+        &["-rq", "-j4", "foo", INPUTS_DIR],
+        "tests/expected/empty.foo",
+    )?;
+    run_no_match(
+        &["-r", "-j4", "nothing matches this", INPUTS_DIR],
+        "tests/expected/empty.foo",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn recursive_unreadable_file() -> Result<()> {
+    let suffix: String = rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(7)
+        .map(char::from)
+        .collect();
+    let dir = std::env::temp_dir().join(format!("grepr-{suffix}"));
+    fs::create_dir_all(dir.join("a"))?;
+    fs::create_dir_all(dir.join("z"))?;
+    for name in ["a/1.txt", "m.txt", "z/2.txt"] {
+        fs::write(dir.join(name), "foo\n")?;
+    }
+    let unreadable = dir.join("a/1.txt");
+    fs::set_permissions(&unreadable, fs::Permissions::from_mode(0o000))?;
+    // Root can read the file anyway, and then there is no error to test.
+    if fs::File::open(&unreadable).is_err() {
+        let root = dir.to_str().unwrap();
+        let expected = format!("{root}/m.txt:foo\n{root}/z/2.txt:foo\n");
+        for jobs in ["-j1", "-j4"] {
+            Command::cargo_bin(PRG)?
+                .args(["-r", jobs, "--sort-files", "foo", root])
+                .assert()
+                .code(2)
+                .stdout(expected.clone())
+                .stderr(format!("{root}/a/1.txt: Permission denied (os error 13)\n"));
+        }
+        Command::cargo_bin(PRG)?
+            .args(["-rs", "foo", root])
+            .assert()
+            .code(2)
+            .stderr("");
+    }
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_zero_jobs() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["-r", "-j0", "foo", INPUTS_DIR])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("invalid value '0'"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn null_data() -> Result<()> {
+    run(
+        &["-zn", "apples", RECORDS],
+        "tests/expected/records.bin.apples.null_data",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn null_data_only_matching() -> Result<()> {
+    run(
+        &["-zo", "apples", RECORDS],
+        "tests/expected/records.bin.apples.null_data.only_matching",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn multiline() -> Result<()> {
+    run(
+        &["-Un", r"fn \w+\(\n[^)]*\)", SIGNATURES],
+        "tests/expected/signatures.rs.multiline",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn multiline_count() -> Result<()> {
+    run(
+        &["-Uc", r"\{\n", SIGNATURES],
+        "tests/expected/signatures.rs.multiline.count",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn multiline_color_context() -> Result<()> {
+    run(
+        &["-Un", "-A1", "--color=always", r"a: i32,\n\)", SIGNATURES],
+        "tests/expected/signatures.rs.multiline.color",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn json() -> Result<()> {
+    run(
+        &["--json", "(?<word>qu\\w+)", FOX, EMPTY],
+        "tests/expected/fox.txt.json",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn json_count() -> Result<()> {
+    run(
+        &["--json", "-c", "ok", LATIN1],
+        "tests/expected/latin1.txt.ok.json.count",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn json_invalid_utf8_context() -> Result<()> {
+    run(
+        &["--json", "-A1", "caf", LATIN1],
+        "tests/expected/latin1.txt.caf.json.context",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn json_recursive() -> Result<()> {
+    run(
+        &["--json", "-r", "--sort-files", "foo", TREE],
+        "tests/expected/tree.foo.json",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_json_files_with_matches() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--json", "-l", "fox", FOX])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("cannot be used with"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn replace() -> Result<()> {
+    run(
+        &["-n", "--replace", "$2 ${adj}", r"(?<adj>\w+) (fox)", FOX],
+        "tests/expected/fox.txt.replace",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn replace_only_matching() -> Result<()> {
+    run(
+        &["-o", "--replace", "<$0>", r"o\w", FOX],
+        "tests/expected/fox.txt.replace.only_matching",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn in_place() -> Result<()> {
+    let suffix: String = rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(7)
+        .map(char::from)
+        .collect();
+    let dir = std::env::temp_dir().join(format!("grepr-{suffix}"));
+    fs::create_dir(&dir)?;
+    let path = dir.join("fox.txt");
+    fs::copy(FOX, &path)?;
+    let path = path.to_str().unwrap();
+    Command::cargo_bin(PRG)?
+        .args(["--in-place", "--backup-suffix", ".bak", "--replace", "cat"])
+        .args(["fox|dog", path])
+        .assert()
+        .success()
+        .stdout(format!("{path}: 2 substitutions in 1 line\n"));
+    assert_eq!(
+        fs::read_to_string(path)?,
+        fs::read_to_string(FOX)?
+            .replace("fox", "cat")
+            .replace("dog", "cat")
+    );
+    assert_eq!(
+        fs::read_to_string(format!("{path}.bak"))?,
+        fs::read_to_string(FOX)?
+    );
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_in_place_stdin() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--in-place", "--replace", "cat", "fox"])
+        .write_stdin("fox")
+        .assert()
+        .code(2)
+        .stderr("-: cannot be edited in place\n");
+    Ok(())
+}
@@ -0,0 +1,8 @@
+fn main(
+    a: i32,
+) {
+}
+
+fn other() {
+    body
+}
@@ -0,0 +1,361 @@
+use std::cmp::Ordering;
+use std::num::NonZeroUsize;
+
+/// How lines are compared, to match how the inputs were sorted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Collation {
+    /// By bytes, like `sort` in the C locale.
+    Bytes,
+    /// By Unicode characters folded to upper case, like `sort -f`.
+    IgnoreCase,
+    /// By the number at the start, like `sort -n`.
+    Numeric,
+    /// By the floating-point number at the start, like `sort -g`.
+    GeneralNumeric,
+    /// By the runs of digits as numbers and the rest as bytes, like `sort -V`.
+    Version,
+    /// By the number at the start with an SI suffix, like `sort -h`.
+    HumanNumeric,
+}
+
+impl Collation {
+    pub fn compare(self, a: &str, b: &str) -> Ordering {
+        match self {
+            Collation::Bytes => a.cmp(b),
+            Collation::IgnoreCase => fold_case(a).cmp(fold_case(b)),
+            Collation::Numeric => compare_numeric(a, b),
+            Collation::GeneralNumeric => compare_general_numeric(a, b),
+            Collation::Version => compare_version(a, b),
+            Collation::HumanNumeric => compare_human_numeric(a, b),
+        }
+    }
+}
+
+/// The part of a line that is compared: a field, split by a separator or else by blanks.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Key<'a> {
+    pub field: Option<NonZeroUsize>,
+    pub separator: Option<&'a str>,
+}
+
+impl Key<'_> {
+    /// The field of `line`, empty if it has too few fields, or the whole line without a field.
+    pub fn of<'b>(&self, line: &'b str) -> &'b str {
+        let Some(field) = self.field else {
+            return line;
+        };
+        let n = field.get() - 1;
+        let field = match self.separator {
+            Some(separator) => line.split(separator).nth(n),
+            None => line.split_whitespace().nth(n),
+        };
+        field.unwrap_or_default()
+    }
+}
+
+/// The characters of `s` in upper case, without allocating a string.
+fn fold_case(s: &str) -> impl Iterator<Item = char> + '_ {
+    s.chars().flat_map(char::to_uppercase)
+}
+
+/// Splits the number at the start of `s`, after blanks, into its sign, integer part without
+/// leading zeros, and fraction without trailing zeros. Without digits, it is zero.
+fn split_number(s: &str) -> (bool, &str, &str) {
+    let s = s.trim_start();
+    let (negative, s) = match s.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, s),
+    };
+    let end = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (integer, rest) = s.split_at(end);
+    let fraction = match rest.strip_prefix('.') {
+        Some(rest) => {
+            let end = rest
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(rest.len());
+            rest[..end].trim_end_matches('0')
+        }
+        None => "",
+    };
+    let integer = integer.trim_start_matches('0');
+    // Zero has no sign.
+    let negative = negative && !(integer.is_empty() && fraction.is_empty());
+    (negative, integer, fraction)
+}
+
+/// Compares runs of digits without leading zeros, by their number of digits first.
+fn compare_digits(a: &str, b: &str) -> Ordering {
+    a.len().cmp(&b.len()).then_with(|| a.cmp(b))
+}
+
+/// Compares the numbers at the start of the lines exactly, whatever their number of digits.
+fn compare_numeric(a: &str, b: &str) -> Ordering {
+    let (a_negative, a_integer, a_fraction) = split_number(a);
+    let (b_negative, b_integer, b_fraction) = split_number(b);
+    let ordering = compare_digits(a_integer, b_integer).then_with(|| a_fraction.cmp(b_fraction));
+    match (a_negative, b_negative) {
+        (false, false) => ordering,
+        (true, true) => ordering.reverse(),
+        (a_negative, b_negative) => b_negative.cmp(&a_negative),
+    }
+}
+
+/// The floating-point number at the start of `s`, after blanks, like `strtod`.
+fn parse_float(s: &str) -> Option<f64> {
+    let s = s.trim_start();
+    let bytes = s.as_bytes();
+    let digits = |start: usize| {
+        bytes[start..]
+            .iter()
+            .take_while(|b| b.is_ascii_digit())
+            .count()
+    };
+    let mut end = usize::from(matches!(bytes.first(), Some(b'+' | b'-')));
+    if let Some(value) = s[end..]
+        .strip_prefix("0x")
+        .or_else(|| s[end..].strip_prefix("0X"))
+        .and_then(parse_hex_float)
+    {
+        return Some(if bytes[0] == b'-' { -value } else { value });
+    }
+    let integer = digits(end);
+    end += integer;
+    let mut fraction = 0;
+    if bytes.get(end) == Some(&b'.') {
+        fraction = digits(end + 1);
+        if integer + fraction > 0 {
+            end += 1 + fraction;
+        }
+    }
+    if integer + fraction == 0 {
+        // Infinities and NaNs, which `parse` reads in any case.
+        let word = s[end..].split(|c: char| !c.is_ascii_alphabetic()).next();
+        let word = word.unwrap_or_default();
+        return ["inf", "infinity", "nan"]
+            .iter()
+            .any(|name| word.eq_ignore_ascii_case(name))
+            .then(|| s[..end + word.len()].parse().ok())
+            .flatten();
+    }
+    if let Some(b'e' | b'E') = bytes.get(end) {
+        let sign = usize::from(matches!(bytes.get(end + 1), Some(b'+' | b'-')));
+        let exponent = digits(end + 1 + sign);
+        if exponent > 0 {
+            end += 1 + sign + exponent;
+        }
+    }
+    s[..end].parse().ok()
+}
+
+/// The hexadecimal floating-point number at the start of `s`, after `0x`, like `1.8p3`.
+fn parse_hex_float(s: &str) -> Option<f64> {
+    let bytes = s.as_bytes();
+    let mut mantissa = 0.0;
+    let mut digits = 0;
+    let mut exponent = 0i32;
+    let mut point = false;
+    let mut end = 0;
+    while let Some(&c) = bytes.get(end) {
+        match c {
+            b'.' if !point => point = true,
+            c if c.is_ascii_hexdigit() => {
+                mantissa = mantissa * 16.0 + f64::from((c as char).to_digit(16)?);
+                digits += 1;
+                if point {
+                    exponent = exponent.saturating_sub(4);
+                }
+            }
+            _ => break,
+        }
+        end += 1;
+    }
+    if digits == 0 {
+        return None;
+    }
+    if let Some(b'p' | b'P') = bytes.get(end) {
+        let rest = &s[end + 1..];
+        let sign = usize::from(matches!(rest.as_bytes().first(), Some(b'+' | b'-')));
+        let count = rest[sign..].bytes().take_while(u8::is_ascii_digit).count();
+        if count > 0 {
+            let negative = rest.starts_with('-');
+            let power = rest[..sign + count].parse::<i32>();
+            let power = power.unwrap_or(if negative { i32::MIN } else { i32::MAX });
+            exponent = exponent.saturating_add(power);
+        }
+    }
+    Some(mantissa * 2f64.powi(exponent))
+}
+
+/// Like `sort -g`: lines without a number come first, then NaNs, then numbers in order.
+fn compare_general_numeric(a: &str, b: &str) -> Ordering {
+    let rank = |n: Option<f64>| match n {
+        None => 0,
+        Some(n) if n.is_nan() => 1,
+        Some(_) => 2,
+    };
+    let (a, b) = (parse_float(a), parse_float(b));
+    rank(a).cmp(&rank(b)).then_with(|| match (a, b) {
+        (Some(a), Some(b)) => a.partial_cmp(&b).unwrap_or(Ordering::Equal),
+        _ => Ordering::Equal,
+    })
+}
+
+/// Compares the runs of digits of the lines as numbers, and the rest as bytes.
+fn compare_version(a: &str, b: &str) -> Ordering {
+    let (mut a, mut b) = (a, b);
+    loop {
+        let (a_text, a_rest) = split_run(a, false);
+        let (b_text, b_rest) = split_run(b, false);
+        let ordering = a_text.cmp(b_text);
+        if ordering.is_ne() {
+            return ordering;
+        }
+        let (a_digits, a_rest) = split_run(a_rest, true);
+        let (b_digits, b_rest) = split_run(b_rest, true);
+        let ordering = compare_digits(
+            a_digits.trim_start_matches('0'),
+            b_digits.trim_start_matches('0'),
+        );
+        if ordering.is_ne() {
+            return ordering;
+        }
+        if a_rest.is_empty() && b_rest.is_empty() {
+            return Ordering::Equal;
+        }
+        (a, b) = (a_rest, b_rest);
+    }
+}
+
+/// Splits `s` after its first run of digits, or of other characters.
+fn split_run(s: &str, digits: bool) -> (&str, &str) {
+    let end = s
+        .find(|c: char| c.is_ascii_digit() != digits)
+        .unwrap_or(s.len());
+    s.split_at(end)
+}
+
+/// The power of the SI suffix after the number at the start of `s`, 0 without one.
+fn suffix_power(s: &str) -> usize {
+    let s = s.trim_start();
+    let s = s.strip_prefix('-').unwrap_or(s);
+    let s = s.trim_start_matches(|c: char| c.is_ascii_digit() || c == '.');
+    s.chars()
+        .next()
+        .and_then(|c| "KMGTPEZYRQ".find(c.to_ascii_uppercase()))
+        .map_or(0, |power| power + 1)
+}
+
+/// Like `sort -h`: by sign, then by suffix, then by number, so that 2K comes after 1000.
+fn compare_human_numeric(a: &str, b: &str) -> Ordering {
+    let sign = |s: &str| match split_number(s) {
+        (true, ..) => -1,
+        (false, "", "") => 0,
+        _ => 1,
+    };
+    let (a_sign, b_sign) = (sign(a), sign(b));
+    let powers = suffix_power(a).cmp(&suffix_power(b));
+    let powers = if a_sign < 0 { powers.reverse() } else { powers };
+    a_sign
+        .cmp(&b_sign)
+        .then(powers)
+        .then_with(|| compare_numeric(a, b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_sorted(collation: Collation, lines: &[&str]) {
+        for pair in lines.windows(2) {
+            assert_eq!(
+                collation.compare(pair[0], pair[1]),
+                Ordering::Less,
+                "{:?}",
+                pair
+            );
+        }
+    }
+
+    #[test]
+    fn test_ignore_case() {
+        assert_sorted(
+            Collation::IgnoreCase,
+            &["apple", "Banana", "ß", "STT", "ÇA", "ça va"],
+        );
+        assert_eq!(
+            Collation::IgnoreCase.compare("straße", "STRASSE"),
+            Ordering::Equal
+        );
+    }
+
+    #[test]
+    fn test_numeric() {
+        assert_sorted(
+            Collation::Numeric,
+            &[
+                "-10",
+                "-9.5",
+                "-1",
+                "abc",
+                "0.5",
+                " 2",
+                "10",
+                "10.01",
+                "99999999999999999999",
+            ],
+        );
+        assert_eq!(Collation::Numeric.compare("-0", ""), Ordering::Equal);
+    }
+
+    #[test]
+    fn test_general_numeric() {
+        assert_sorted(
+            Collation::GeneralNumeric,
+            &[
+                "abc", "nan", "-inf", "-1e3", "-0x10", "-2", "0.5", "0x1p1", "0x10", "1e2",
+                "0x1.8p7", "Infinity",
+            ],
+        );
+        assert_eq!(
+            Collation::GeneralNumeric.compare("1e", "1.0"),
+            Ordering::Equal
+        );
+    }
+
+    #[test]
+    fn test_version() {
+        assert_sorted(
+            Collation::Version,
+            &[
+                "file", "file1", "file2", "file10", "file10a", "file10b", "v1.2", "v1.10",
+            ],
+        );
+    }
+
+    #[test]
+    fn test_human_numeric() {
+        assert_sorted(
+            Collation::HumanNumeric,
+            &[
+                "-1G", "-2K", "-1000", "0", "1", "1000", "1.5K", "2K", "1M", "1G",
+            ],
+        );
+    }
+
+    #[test]
+    fn test_key() {
+        let key = Key {
+            field: NonZeroUsize::new(2),
+            separator: None,
+        };
+        assert_eq!(key.of("a  b c"), "b");
+        assert_eq!(key.of("a"), "");
+        let key = Key {
+            separator: Some(","),
+            ..key
+        };
+        assert_eq!(key.of("a,,b"), "");
+        assert_eq!(key.of("a b,c"), "c");
+        assert_eq!(Key::default().of("a b"), "a b");
+    }
+}
@@ -112,6 +112,63 @@ where
     try_merge_by(left, right, move |l, r| ordering_to_choice(cmp(l, r)))
 }
 
+/// An iterator that checks that the `Ok` items of an iterator are in order. Before each item out
+/// of order, it yields the error made by `disorder` from the item's number, starting at 1.
+pub struct TryCheckOrder<I, F, D>
+where
+    I: Iterator,
+{
+    iter: Peekable<I>,
+    cmp: F,
+    disorder: Option<D>,
+    /// The number of items yielded.
+    count: usize,
+    pending: bool,
+}
+
+impl<I, T, E, F, D> Iterator for TryCheckOrder<I, F, D>
+where
+    I: Iterator<Item = Result<T, E>>,
+    F: FnMut(&T, &T) -> Ordering,
+    D: FnMut(usize) -> E,
+{
+    type Item = Result<T, E>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let (true, Some(disorder)) = (self.pending, &mut self.disorder) {
+            self.pending = false;
+            return Some(Err(disorder(self.count + 1)));
+        }
+        let item = self.iter.next()?;
+        self.count += 1;
+        // The next item is peeked at, so that the previous one need not be kept.
+        if let (Some(_), Ok(current), Some(Ok(next))) = (&self.disorder, &item, self.iter.peek()) {
+            self.pending = (self.cmp)(current, next) == Ordering::Greater;
+        }
+        Some(item)
+    }
+}
+
+/// Checks the order of `iter` with `cmp`, unless `disorder` is `None`.
+pub fn try_check_order_by<I, T, E, F, D>(
+    iter: I,
+    cmp: F,
+    disorder: Option<D>,
+) -> TryCheckOrder<I, F, D>
+where
+    I: Iterator<Item = Result<T, E>>,
+    F: FnMut(&T, &T) -> Ordering,
+    D: FnMut(usize) -> E,
+{
+    TryCheckOrder {
+        iter: iter.peekable(),
+        cmp,
+        disorder,
+        count: 0,
+        pending: false,
+    }
+}
+
 #[allow(dead_code)]
 pub fn merge<L, R>(left: L, right: R) -> impl Iterator<Item = (Option<L::Item>, Option<R::Item>)>
 where
@@ -187,6 +244,29 @@ mod tests {
         assert_eq!(actual, expected);
     }
 
+    #[test]
+    fn test_try_check_order_by() {
+        let input = [Ok(1), Ok(3), Ok(2), Err(0), Ok(4), Ok(4), Ok(1)];
+        let expected = vec![
+            Ok(1),
+            Ok(3),
+            Err(3),
+            Ok(2),
+            Err(0),
+            Ok(4),
+            Ok(4),
+            Err(7),
+            Ok(1),
+        ];
+        let actual = try_check_order_by(input.into_iter(), i32::cmp, Some(|line| line as i32))
+            .collect::<Vec<_>>();
+        assert_eq!(actual, expected);
+
+        let actual = try_check_order_by(input.into_iter(), i32::cmp, None::<fn(usize) -> i32>)
+            .collect::<Vec<_>>();
+        assert_eq!(actual, input);
+    }
+
     #[test]
     fn test_try_merge_err() {
         let left = [Err(2), Ok(4), Err(6)].into_iter();
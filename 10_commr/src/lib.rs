@@ -1,10 +1,14 @@
-use clap::Parser;
+use clap::{Parser, ValueEnum, builder::NonEmptyStringValueParser};
+use serde::Serialize;
 use std::cmp::Ordering;
 use std::fs::File;
-use std::io::{self, BufRead, BufReader};
+use std::io::{self, BufRead, BufReader, Write};
+use std::num::NonZeroUsize;
 use std::path::{Path, PathBuf};
 use thiserror::Error;
 
+mod compare;
+use compare::{Collation, Key};
 mod iter;
 
 #[derive(Error, Debug)]
@@ -14,6 +18,18 @@ pub enum MyError {
 
     #[error("Both input files cannot be STDIN (\"-\")")]
     BothInputStdin,
+
+    #[error("file {0} is not in sorted order at line {1}")]
+    Disorder(usize, usize),
+
+    #[error("input is not in sorted order")]
+    InputNotSorted,
+
+    #[error("--total cannot be used with --format csv")]
+    CsvTotal,
+
+    #[error("write error: {0}")]
+    WriteError(io::Error),
 }
 
 pub type MyResult<T> = Result<T, MyError>;
@@ -40,18 +56,132 @@ pub struct Args {
     supresses_third_column: bool,
 
     /// Ignore case when comparing lines
-    #[arg(short, long)]
+    #[arg(short, long, group = "collation")]
     ignore_case: bool,
 
+    /// Compare by the numbers at the start of the lines
+    #[arg(
+        short,
+        long = "numeric-sort",
+        visible_alias = "numeric",
+        group = "collation"
+    )]
+    numeric: bool,
+
+    /// Compare by the floating-point numbers at the start of the lines
+    #[arg(
+        short,
+        long = "general-numeric-sort",
+        visible_alias = "general-numeric",
+        group = "collation"
+    )]
+    general_numeric: bool,
+
+    /// Compare the runs of digits in the lines as numbers (natural sort)
+    #[arg(long = "version-sort", group = "collation")]
+    version_sort: bool,
+
+    /// Compare by the numbers at the start of the lines, with SI suffixes (e.g., 2K 1G)
+    #[arg(
+        long = "human-numeric-sort",
+        visible_alias = "human-numeric",
+        group = "collation"
+    )]
+    human_numeric: bool,
+
+    /// Compare only field N of the lines
+    #[arg(short, long = "key", value_name = "N")]
+    key: Option<NonZeroUsize>,
+
+    /// Split the fields at SEP rather than blanks
+    #[arg(
+        short = 't',
+        long = "field-separator",
+        value_name = "SEP",
+        requires = "key",
+        value_parser = NonEmptyStringValueParser::new()
+    )]
+    separator: Option<String>,
+
     /// Use DELIM for column separator
     #[arg(short, long, name = "DELIM", default_value = "\t")]
     delimiter: String,
+
+    /// Fail as soon as an input is not in sorted order
+    #[arg(long, overrides_with = "nocheck_order")]
+    check_order: bool,
+
+    /// Do not check that the inputs are in sorted order
+    #[arg(long, overrides_with = "check_order")]
+    nocheck_order: bool,
+
+    /// Print the number of lines in each column at the end
+    #[arg(long)]
+    total: bool,
+
+    /// Output format
+    #[arg(long, value_enum, default_value_t = Format::Text)]
+    format: Format,
+}
+
+/// How the lines are printed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// In tab-indented columns
+    Text,
+    /// As JSON objects, one per line, tagged with their column
+    Json,
+    /// As CSV records tagged with their column, after a header
+    Csv,
+}
+
+/// The column of a line, by the files it is in.
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Only {
+    File1,
+    File2,
+    Both,
+}
+
+/// A line with its column, for `--format json` and `--format csv`. A line in both files that
+/// differs in file2, by case or outside the key, has the line of file2 as `line2`.
+#[derive(Debug, Serialize)]
+struct Record<'a> {
+    only: Only,
+    line: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    line2: Option<&'a str>,
+}
+
+/// The number of lines in each column, for `--total` with `--format json`, as
+/// `{"total": ...}`.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Summary {
+    Total {
+        file1: usize,
+        file2: usize,
+        both: usize,
+    },
+}
+
+/// How to check that the inputs are sorted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OrderCheck {
+    /// Like GNU comm, warn once for each file, but only after a line unique to a file, and fail
+    /// at the end.
+    Default,
+    /// Fail at the first line out of order.
+    Strict,
+    None,
 }
 
 impl Args {
     pub fn check(self) -> MyResult<Args> {
         match (self.file1.to_str(), self.file2.to_str()) {
             (Some("-"), Some("-")) => Err(MyError::BothInputStdin),
+            _ if self.total && self.format == Format::Csv => Err(MyError::CsvTotal),
             _ => Ok(self),
         }
     }
@@ -64,12 +194,47 @@ impl Args {
         ]
     }
 
-    fn comparator(&self) -> impl FnMut(&String, &String) -> Ordering + '_ {
-        |a: &String, b: &String| {
-            if self.ignore_case {
-                a.to_uppercase().cmp(&b.to_uppercase())
+    fn order_check(&self) -> OrderCheck {
+        if self.check_order {
+            OrderCheck::Strict
+        } else if self.nocheck_order {
+            OrderCheck::None
+        } else {
+            OrderCheck::Default
+        }
+    }
+
+    fn collation(&self) -> Collation {
+        if self.ignore_case {
+            Collation::IgnoreCase
+        } else if self.numeric {
+            Collation::Numeric
+        } else if self.general_numeric {
+            Collation::GeneralNumeric
+        } else if self.version_sort {
+            Collation::Version
+        } else if self.human_numeric {
+            Collation::HumanNumeric
+        } else {
+            Collation::Bytes
+        }
+    }
+
+    fn comparator(&self) -> impl Fn(&String, &String) -> Ordering + Copy + '_ {
+        let collation = self.collation();
+        let key = Key {
+            field: self.key,
+            separator: self.separator.as_deref(),
+        };
+        // Like sort, lines equal by number or version are compared by bytes as a last resort,
+        // unless only a key is compared. Lines equal but for case are the same line with -i.
+        let last_resort = key.field.is_none() && collation != Collation::IgnoreCase;
+        move |a: &String, b: &String| {
+            let ordering = collation.compare(key.of(a), key.of(b));
+            if last_resort {
+                ordering.then_with(|| a.cmp(b))
             } else {
-                a.cmp(b)
+                ordering
             }
         }
     }
@@ -78,6 +243,15 @@ impl Args {
 struct Columns<const N: usize>([Option<String>; N]);
 
 impl<const N: usize> Columns<N> {
+    /// The column of the line, and the line.
+    fn line(&self) -> (usize, &str) {
+        self.0
+            .iter()
+            .enumerate()
+            .find_map(|(i, c)| Some((i, c.as_deref()?)))
+            .expect("a line in a column")
+    }
+
     fn filter(self, cond: &[bool]) -> Option<Vec<Option<String>>> {
         let filtered = self
             .0
@@ -115,16 +289,26 @@ fn lines(path: &PathBuf) -> MyResult<impl Iterator<Item = MyResult<String>> + '_
     })
 }
 
+/// Merges the inputs into columns, with the line of file2 when it differs from the line of file1
+/// in the third column. With `check_order`, a `MyError::Disorder` comes before each line out of
+/// the order of `comparator`.
 fn comm(
     input1: impl Iterator<Item = MyResult<String>>,
     input2: impl Iterator<Item = MyResult<String>>,
-    comparator: impl FnMut(&String, &String) -> Ordering,
-) -> impl Iterator<Item = MyResult<Columns<3>>> {
+    comparator: impl Fn(&String, &String) -> Ordering + Copy,
+    check_order: bool,
+) -> impl Iterator<Item = MyResult<(Columns<3>, Option<String>)>> {
+    let disorder = |file| check_order.then_some(move |line| MyError::Disorder(file, line));
+    let input1 = iter::try_check_order_by(input1, comparator, disorder(1));
+    let input2 = iter::try_check_order_by(input2, comparator, disorder(2));
     iter::try_merge_ordered_by(input1, input2, comparator).map(|x| {
         x.map(|(left, right)| match (left, right) {
-            (Some(l), None) => Columns([Some(l), None, None]),
-            (None, Some(r)) => Columns([None, Some(r), None]),
-            (Some(l), Some(_)) => Columns([None, None, Some(l)]),
+            (Some(l), None) => (Columns([Some(l), None, None]), None),
+            (None, Some(r)) => (Columns([None, Some(r), None]), None),
+            (Some(l), Some(r)) => {
+                let r = (r != l).then_some(r);
+                (Columns([None, None, Some(l)]), r)
+            }
             (None, None) => unreachable!(),
         })
     })
@@ -140,14 +324,125 @@ fn format_columns(columns: &[Option<String>], delim: &String) -> String {
         .to_owned()
 }
 
+/// Writes the lines, as columns of text or as records tagged with their column.
+enum Printer<'a> {
+    Text(&'a String),
+    Json(io::Stdout),
+    Csv(Box<csv::Writer<io::Stdout>>),
+}
+
+impl Printer<'_> {
+    /// A printer for the format of `args`. In CSV, the header is written even without records.
+    fn new(args: &Args) -> io::Result<Printer<'_>> {
+        Ok(match args.format {
+            Format::Text => Printer::Text(&args.delimiter),
+            Format::Json => Printer::Json(io::stdout()),
+            Format::Csv => {
+                let mut writer = csv::WriterBuilder::new()
+                    .has_headers(false)
+                    .from_writer(io::stdout());
+                writer.write_record(["only", "line", "line2"])?;
+                Printer::Csv(Box::new(writer))
+            }
+        })
+    }
+
+    /// Writes the line of `columns`, unless its column is filtered out, and in JSON or CSV the
+    /// differing line of file2 in the third column.
+    fn write(
+        &mut self,
+        columns: Columns<3>,
+        line2: Option<&str>,
+        column_filter: &[bool],
+    ) -> io::Result<()> {
+        let (column, line) = columns.line();
+        if !column_filter[column] {
+            return Ok(());
+        }
+        let only = [Only::File1, Only::File2, Only::Both][column];
+        let record = Record { only, line, line2 };
+        match self {
+            Printer::Text(delim) => {
+                if let Some(columns) = columns.filter(column_filter) {
+                    println!("{}", format_columns(&columns, delim));
+                }
+                Ok(())
+            }
+            Printer::Json(out) => {
+                serde_json::to_writer(&mut *out, &record)?;
+                writeln!(out)
+            }
+            // Every record has all the fields of the header.
+            Printer::Csv(writer) => {
+                let line2 = record.line2.unwrap_or_default();
+                Ok(writer.serialize((record.only, record.line, line2))?)
+            }
+        }
+    }
+
+    /// Writes the number of lines in each column, like GNU comm in text.
+    fn write_total(&mut self, counts: [usize; 3]) -> io::Result<()> {
+        match self {
+            Printer::Text(delim) => {
+                let counts = counts.map(|count| count.to_string());
+                println!("{}{delim}total", counts.join(delim));
+                Ok(())
+            }
+            Printer::Json(out) => {
+                let [file1, file2, both] = counts;
+                let total = Summary::Total { file1, file2, both };
+                serde_json::to_writer(&mut *out, &total)?;
+                writeln!(out)
+            }
+            Printer::Csv(_) => unreachable!("--total is checked against --format csv"),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Printer::Text(_) => Ok(()),
+            Printer::Json(out) => out.flush(),
+            Printer::Csv(writer) => writer.flush(),
+        }
+    }
+}
+
 pub fn run(args: Args) -> MyResult<()> {
     let column_filter = args.column_filter();
+    let order_check = args.order_check();
     let input1 = lines(&args.file1)?;
     let input2 = lines(&args.file2)?;
-    for columns in comm(input1, input2, args.comparator()) {
-        if let Some(columns) = columns?.filter(&column_filter) {
-            println!("{}", format_columns(&columns, &args.delimiter));
-        }
+    let check_order = order_check != OrderCheck::None;
+    // The files reported out of order, and whether a line unique to a file was seen.
+    let mut unsorted = [false; 2];
+    let mut unpairable = false;
+    // The number of lines in each column, including the suppressed ones, like GNU comm.
+    let mut counts = [0; 3];
+    let mut printer = Printer::new(&args).map_err(MyError::WriteError)?;
+    for columns in comm(input1, input2, args.comparator(), check_order) {
+        let (columns, line2) = match columns {
+            Err(e @ MyError::Disorder(file, _)) if order_check == OrderCheck::Default => {
+                if unpairable && !unsorted[file - 1] {
+                    eprintln!("{}", e);
+                    unsorted[file - 1] = true;
+                }
+                continue;
+            }
+            columns => columns?,
+        };
+        let (column, _) = columns.line();
+        unpairable |= column != 2;
+        counts[column] += 1;
+        printer
+            .write(columns, line2.as_deref(), &column_filter)
+            .map_err(MyError::WriteError)?;
+    }
+    if args.total {
+        printer.write_total(counts).map_err(MyError::WriteError)?;
+    }
+    printer.flush().map_err(MyError::WriteError)?;
+    if unsorted.contains(&true) {
+        return Err(MyError::InputNotSorted);
     }
     Ok(())
 }
@@ -10,6 +10,22 @@ const EMPTY: &str = "tests/inputs/empty.txt";
 const FILE1: &str = "tests/inputs/file1.txt";
 const FILE2: &str = "tests/inputs/file2.txt";
 const BLANK: &str = "tests/inputs/blank.txt";
+const UNSORTED1: &str = "tests/inputs/unsorted1.txt";
+const UNSORTED2: &str = "tests/inputs/unsorted2.txt";
+const MIXED1: &str = "tests/inputs/mixed1.txt";
+const MIXED2: &str = "tests/inputs/mixed2.txt";
+const NUMBERS1: &str = "tests/inputs/numbers1.txt";
+const NUMBERS2: &str = "tests/inputs/numbers2.txt";
+const FLOATS1: &str = "tests/inputs/floats1.txt";
+const FLOATS2: &str = "tests/inputs/floats2.txt";
+const VERSIONS1: &str = "tests/inputs/versions1.txt";
+const VERSIONS2: &str = "tests/inputs/versions2.txt";
+const SIZES1: &str = "tests/inputs/sizes1.txt";
+const SIZES2: &str = "tests/inputs/sizes2.txt";
+const TIES1: &str = "tests/inputs/ties1.txt";
+const TIES2: &str = "tests/inputs/ties2.txt";
+const KEYED1: &str = "tests/inputs/keyed1.csv";
+const KEYED2: &str = "tests/inputs/keyed2.csv";
 
 // --------------------------------------------------
 #[test]
@@ -338,3 +354,251 @@ fn file1_file2_123_delim() -> Result<()> {
 fn blank_file1() -> Result<()> {
     run(&[BLANK, FILE1], "tests/expected/blank_file1.out")
 }
+
+// --------------------------------------------------
+fn run_unsorted(args: &[&str], expected_file: &str, expected_stderr: &str) -> Result<()> {
+    let expected = fs::read_to_string(expected_file)?;
+    Command::cargo_bin(PRG)?
+        .args(args)
+        .assert()
+        .code(1)
+        .stdout(expected)
+        .stderr(expected_stderr.to_string());
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn unsorted_default() -> Result<()> {
+    run_unsorted(
+        &[UNSORTED1, UNSORTED2],
+        "tests/expected/unsorted1_unsorted2.out",
+        "file 1 is not in sorted order at line 3\ninput is not in sorted order\n",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn unsorted_check_order() -> Result<()> {
+    run_unsorted(
+        &["--check-order", UNSORTED1, UNSORTED2],
+        "tests/expected/unsorted1_unsorted2.check_order.out",
+        "file 1 is not in sorted order at line 3\n",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn unsorted_file2_check_order() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--check-order", UNSORTED2, UNSORTED1])
+        .assert()
+        .code(1)
+        .stderr("file 2 is not in sorted order at line 3\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn unsorted_nocheck_order() -> Result<()> {
+    run(
+        &["--check-order", "--nocheck-order", UNSORTED1, UNSORTED2],
+        "tests/expected/unsorted1_unsorted2.nocheck_order.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn unsorted_all_pairable() -> Result<()> {
+    // Like GNU comm, a file out of order is only reported after a line unique to a file.
+    run(
+        &[UNSORTED1, UNSORTED1],
+        "tests/expected/unsorted1_unsorted1.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn check_order_ignore_case() -> Result<()> {
+    run(
+        &["--check-order", "-i", MIXED1, MIXED2],
+        "tests/expected/mixed1_mixed2.check_order.i.out",
+    )?;
+    Command::cargo_bin(PRG)?
+        .args(["--check-order", MIXED1, MIXED2])
+        .assert()
+        .code(1)
+        .stderr("file 1 is not in sorted order at line 2\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn numbers_numeric() -> Result<()> {
+    run(
+        &["--check-order", "-n", NUMBERS1, NUMBERS2],
+        "tests/expected/numbers1_numbers2.n.out",
+    )?;
+    Command::cargo_bin(PRG)?
+        .args(["--check-order", NUMBERS1, NUMBERS2])
+        .assert()
+        .code(1)
+        .stderr("file 1 is not in sorted order at line 2\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn floats_general_numeric() -> Result<()> {
+    run(
+        &["--check-order", "-g", FLOATS1, FLOATS2],
+        "tests/expected/floats1_floats2.g.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn versions_version_sort() -> Result<()> {
+    run(
+        &["--check-order", "--version-sort", VERSIONS1, VERSIONS2],
+        "tests/expected/versions1_versions2.version_sort.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn sizes_human_numeric() -> Result<()> {
+    run(
+        &["--check-order", "--human-numeric", SIZES1, SIZES2],
+        "tests/expected/sizes1_sizes2.human_numeric.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn keyed_field_separator() -> Result<()> {
+    run(
+        &["--check-order", "-k", "2", "-t", ",", KEYED1, KEYED2],
+        "tests/expected/keyed1_keyed2.k2.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn ties_numeric_by_bytes() -> Result<()> {
+    run(
+        &["--check-order", "-n", TIES1, TIES2],
+        "tests/expected/ties1_ties2.n.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn ties_key_numeric() -> Result<()> {
+    run(
+        &["--check-order", "-n", "-k", "1", TIES1, TIES2],
+        "tests/expected/ties1_ties2.n.k1.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_conflicting_collations() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["-n", "-i", FILE1, FILE2])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+    Command::cargo_bin(PRG)?
+        .args(["-t", ",", FILE1, FILE2])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--key <N>"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_empty_separator() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["-k", "1", "-t", "", FILE1, FILE2])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("a value is required"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn file1_file2_total() -> Result<()> {
+    run(
+        &[FILE1, FILE2, "--total"],
+        "tests/expected/file1_file2.total.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn file1_file2_12_delim_total() -> Result<()> {
+    // Like GNU comm, the suppressed columns are counted.
+    run(
+        &[FILE1, FILE2, "-12", "-d", ":", "--total"],
+        "tests/expected/file1_file2.12.delim.total.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn file1_file2_json_total() -> Result<()> {
+    run(
+        &[FILE1, FILE2, "--format", "json", "--total"],
+        "tests/expected/file1_file2.json.total.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn file1_file2_3_json() -> Result<()> {
+    run(
+        &[FILE1, FILE2, "-3", "--format", "json"],
+        "tests/expected/file1_file2.3.json.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn keyed_csv() -> Result<()> {
+    run(
+        &[KEYED1, KEYED2, "-k", "2", "-t", ",", "--format", "csv"],
+        "tests/expected/keyed1_keyed2.k2.csv.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn keyed_json() -> Result<()> {
+    run(
+        &[KEYED1, KEYED2, "-k", "2", "-t", ",", "--format", "json"],
+        "tests/expected/keyed1_keyed2.k2.json.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn file1_file1_3_csv() -> Result<()> {
+    run(
+        &[FILE1, FILE1, "-3", "--format", "csv"],
+        "tests/expected/file1_file1.3.csv.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_csv_total() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args([FILE1, FILE2, "--format", "csv", "--total"])
+        .assert()
+        .failure()
+        .stderr("--total cannot be used with --format csv\n");
+    Ok(())
+}